@@ -0,0 +1,203 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::error::ResourceError;
+use crate::fence::GpuFence;
+use crate::render_device::RenderDevice;
+use crate::render_resource::{BufferCreateInfo, BufferInfo, RenderResource, ResourceFlag};
+
+pub struct Buffer {
+    pub info: BufferInfo,
+    pub buffer: wgpu::Buffer,
+}
+
+impl Buffer {
+    // Buffer::new() returns Arc<Buffer>
+    pub fn new(device: &RenderDevice, create_info: BufferCreateInfo, name: &str) -> Arc<Buffer> {
+        let (buffer, allocation_size) =
+            device.measure_allocation(create_info.request_size(), || {
+                device.device().create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(name),
+                    size: create_info.size,
+                    usage: create_info.usage,
+                    mapped_at_creation: false,
+                })
+            });
+
+        Arc::new(Buffer {
+            buffer,
+            info: BufferInfo {
+                name: name.to_string(),
+                base_info: crate::render_resource::ResourceInfo {
+                    flags: ResourceFlag::NONE,
+                    request_size: create_info.request_size(),
+                    allocation_size,
+                },
+                usage: create_info.usage,
+            },
+        })
+    }
+
+    // Resolves with the whole buffer content once the map callback fires.
+    // Native targets drive the callback by polling the device, on the web the
+    // browser event loop does it, so the future must be awaited rather than blocked on.
+    pub fn map_read_async<'a>(
+        &'a self,
+        device: &'a RenderDevice,
+    ) -> impl Future<Output = Result<Vec<u8>, ResourceError>> + 'a {
+        self.map_read(device, None)
+    }
+
+    // Same as map_read_async, but native targets only wait for the fenced submission
+    // instead of every submission queued so far.
+    pub fn map_read_async_after<'a>(
+        &'a self,
+        device: &'a RenderDevice,
+        fence: &GpuFence,
+    ) -> impl Future<Output = Result<Vec<u8>, ResourceError>> + 'a {
+        self.map_read(device, Some(fence.submission_index().clone()))
+    }
+
+    fn map_read<'a>(
+        &'a self,
+        device: &'a RenderDevice,
+        submission_index: Option<wgpu::SubmissionIndex>,
+    ) -> MapRead<'a> {
+        let state = Arc::new(Mutex::new(MapReadState::default()));
+        // map_async on a buffer without MAP_READ raises a validation error instead of
+        // calling back, so reject it up front and let the future resolve to the error.
+        if !self.info.usage.contains(wgpu::BufferUsages::MAP_READ) {
+            state.lock().unwrap().result = Some(Err(ResourceError::MapFailed(format!(
+                "{} requires MAP_READ usage but has {:?}",
+                self.info.name, self.info.usage
+            ))));
+            return MapRead {
+                buffer: self,
+                device,
+                submission_index,
+                state,
+            };
+        }
+
+        let callback_state = state.clone();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let mut state = callback_state.lock().unwrap();
+                state.result =
+                    Some(result.map_err(|err| ResourceError::MapFailed(err.to_string())));
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            });
+        MapRead {
+            buffer: self,
+            device,
+            submission_index,
+            state,
+        }
+    }
+}
+
+#[derive(Default)]
+struct MapReadState {
+    result: Option<Result<(), ResourceError>>,
+    waker: Option<Waker>,
+}
+
+struct MapRead<'a> {
+    buffer: &'a Buffer,
+    device: &'a RenderDevice,
+    // None waits for everything submitted so far.
+    submission_index: Option<wgpu::SubmissionIndex>,
+    state: Arc<Mutex<MapReadState>>,
+}
+
+impl Future for MapRead<'_> {
+    type Output = Result<Vec<u8>, ResourceError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(err) = self.device.device().poll(wgpu::PollType::Wait {
+            submission_index: self.submission_index.clone(),
+            timeout: None,
+        }) {
+            return Poll::Ready(Err(ResourceError::MapFailed(err.to_string())));
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = (self.device, &self.submission_index);
+
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(Ok(())) => {
+                let data = self.buffer.buffer.slice(..).get_mapped_range().to_vec();
+                self.buffer.buffer.unmap();
+                Poll::Ready(Ok(data))
+            }
+            Some(Err(err)) => Poll::Ready(Err(err)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl RenderResource for Buffer {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+    fn resource_flag(&self) -> ResourceFlag {
+        self.info.base_info.flags
+    }
+    fn request_size(&self) -> u64 {
+        self.info.base_info.request_size
+    }
+    fn allocation_size(&self) -> u64 {
+        self.info.base_info.allocation_size
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::render_device::test_device;
+
+    #[test]
+    fn map_read_returns_written_data() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let create_info = BufferCreateInfo {
+            size: 16,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        };
+        let buffer = Buffer::new(&device, create_info, "readback");
+        let data: Vec<u8> = (0..16).collect();
+        device
+            .command_queue()
+            .write_buffer(&buffer.buffer, 0, &data);
+        device.submit([]);
+
+        let mapped = pollster::block_on(buffer.map_read_async(&device)).unwrap();
+        assert_eq!(mapped, data);
+    }
+
+    #[test]
+    fn map_read_requires_map_read_usage() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let create_info = BufferCreateInfo {
+            size: 16,
+            usage: wgpu::BufferUsages::COPY_DST,
+        };
+        let buffer = Buffer::new(&device, create_info, "not_mappable");
+        assert!(matches!(
+            pollster::block_on(buffer.map_read_async(&device)),
+            Err(ResourceError::MapFailed(_))
+        ));
+    }
+}
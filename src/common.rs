@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+// Calls f until it succeeds, at most `attempts` times (f always runs at least once).
+// The wait between attempts starts at `backoff` and doubles after every failure.
+// Returns the first success or the error of the last attempt.
+pub fn retry<T, E: std::fmt::Debug>(
+    attempts: u32,
+    backoff: Duration,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let attempts = attempts.max(1);
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < attempts => {
+                log::warn!(
+                    "Attempt {}/{} failed {:?}, retry after {:?}",
+                    attempt,
+                    attempts,
+                    err,
+                    delay
+                );
+                // The web main thread can't block, so retries happen back to back there.
+                #[cfg(not(target_arch = "wasm32"))]
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            // Reporting the final failure is left to the caller.
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_returns_first_success() {
+        let mut calls = 0;
+        let result: Result<u32, ()> = retry(3, Duration::ZERO, || {
+            calls += 1;
+            Ok(7)
+        });
+        assert_eq!(result, Ok(7));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn retry_succeeds_after_failures() {
+        let mut calls = 0;
+        let result = retry(3, Duration::ZERO, || {
+            calls += 1;
+            if calls < 3 {
+                Err(calls)
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn retry_returns_last_error() {
+        let mut calls = 0;
+        let result: Result<(), u32> = retry(3, Duration::ZERO, || {
+            calls += 1;
+            Err(calls)
+        });
+        assert_eq!(result, Err(3));
+        assert_eq!(calls, 3);
+    }
+}
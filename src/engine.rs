@@ -1,4 +1,7 @@
+#[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use winit::{
     application::ApplicationHandler,
@@ -7,113 +10,364 @@ use winit::{
     window::{Window, WindowId},
 };
 
-use crate::{error::DeviceError, render_device::RenderDevice, swapchain::SwapChain};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::texture::read_texture_pixels;
+use crate::{
+    common::retry,
+    error::DeviceError,
+    render_device::{RenderDevice, RenderDeviceConfig},
+    render_pass::{ClearPass, RenderPass, RenderTarget},
+    swapchain::{PresentStats, SwapChain, SwapChainConfig},
+};
+#[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+use crate::{error::ResourceError, render_pass::FullscreenPass, shader_watcher::ShaderWatcher};
+
+pub struct EngineConfig {
+    pub device: RenderDeviceConfig,
+    pub swapchain: SwapChainConfig,
+    // Base log level, RUST_LOG still takes precedence when set.
+    pub log_level: log::LevelFilter,
+    // Per-module overrides applied on top of log_level.
+    pub module_log_levels: Vec<(String, log::LevelFilter)>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            device: RenderDeviceConfig::default(),
+            swapchain: SwapChainConfig::default(),
+            log_level: log::LevelFilter::Info,
+            // We keep wgpu at Error level, as it's very noisy.
+            module_log_levels: vec![
+                ("wgpu_core".to_string(), log::LevelFilter::Error),
+                ("wgpu_hal".to_string(), log::LevelFilter::Error),
+                ("naga".to_string(), log::LevelFilter::Error),
+            ],
+        }
+    }
+}
+
+// Tightly packed pixels of one presented surface image, in `format` byte order.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub pixels: Vec<u8>,
+}
 
 pub struct Engine {
+    pub(crate) config: EngineConfig,
     pub(crate) render_device: RenderDevice,
     pub(crate) window: Option<Arc<Window>>,
     // swapchain must have weak-ref to window handle. if window handle destroyed, swapchain is no more available.
     pub(crate) swapchain: Option<SwapChain>,
+    pub(crate) suspended: bool,
+    pub(crate) present_stats: PresentStats,
+    // Swizzle flag of a pending capture_frame() request, served by the next render().
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) capture_request: Option<bool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) captured_frame: Option<CapturedFrame>,
+    #[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+    pub(crate) shader_watcher: Option<ShaderWatcher>,
+    // Fullscreen passes driven by watched shader files, drawn in registration order.
+    #[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+    pub(crate) shader_passes: Vec<(PathBuf, FullscreenPass)>,
 }
 
 impl Engine {
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn new() -> Result<Engine, DeviceError> {
+        Engine::with_config(EngineConfig::default())
+    }
+
+    // Blocking on the wasm main thread never lets the adapter request resolve,
+    // so the web target must use new_async instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_config(config: EngineConfig) -> Result<Engine, DeviceError> {
+        pollster::block_on(Engine::new_async(config))
+    }
+
+    pub async fn new_async(config: EngineConfig) -> Result<Engine, DeviceError> {
         Ok(Engine {
-            render_device: pollster::block_on(RenderDevice::new())?,
+            render_device: RenderDevice::with_config(config.device).await?,
+            config,
             window: None,
             swapchain: None,
+            suspended: false,
+            present_stats: PresentStats::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            capture_request: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            captured_frame: None,
+            #[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+            shader_watcher: None,
+            #[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+            shader_passes: Vec::new(),
         })
     }
 
+    // Draws the shader as a fullscreen pass every frame and reloads it whenever the file changes.
+    #[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+    pub fn watch_shader(&mut self, path: impl AsRef<Path>) -> Result<(), ResourceError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|err| {
+            ResourceError::ShaderLoadFailed(format!("{} {}", path.display(), err))
+        })?;
+
+        let watcher = match self.shader_watcher.take() {
+            Some(watcher) => watcher,
+            None => ShaderWatcher::new()?,
+        };
+        let path = self.shader_watcher.insert(watcher).watch(path)?;
+
+        let mut pass = FullscreenPass::new(&source, &path.display().to_string());
+        pass.reload(&self.render_device, &source)?;
+        self.shader_passes.push((path, pass));
+        Ok(())
+    }
+
+    #[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+    fn reload_shaders(&mut self) {
+        let Some(watcher) = &self.shader_watcher else {
+            return;
+        };
+        let render_device = &self.render_device;
+        let shader_passes = &mut self.shader_passes;
+        watcher.poll(|path, source| {
+            for (_, pass) in shader_passes
+                .iter_mut()
+                .filter(|(watched, _)| watched == path)
+            {
+                match pass.reload(render_device, source) {
+                    Ok(()) => log::info!("Shader {} reloaded", path.display()),
+                    Err(err) => log::error!("{}", err),
+                }
+            }
+        });
+    }
+
+    // Copies the next presented frame back to the CPU, see take_captured_frame().
+    // The surface must be configured with COPY_SRC in SwapChainConfig::surface_usage.
+    // With swizzle_to_rgba, BGRA8 surfaces are reordered to RGBA8 on the CPU, which costs
+    // about one more copy of the image.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture_frame(&mut self, swizzle_to_rgba: bool) {
+        self.capture_request = Some(swizzle_to_rgba);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn take_captured_frame(&mut self) -> Option<CapturedFrame> {
+        self.captured_frame.take()
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    // The native window backing the surface can be destroyed while suspended (Android),
+    // so the swapchain must release it now and is recreated on resume.
+    fn suspend(&mut self) {
+        log::info!("Release swapchain as the app is suspended");
+        self.suspended = true;
+        self.swapchain = None;
+    }
+
+    fn resume(&mut self) {
+        self.suspended = false;
+    }
+
     pub fn render(&mut self) -> Result<(), DeviceError> {
+        // No surface to present to until resumed.
+        if self.suspended {
+            return Ok(());
+        }
+
+        #[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+        self.reload_shaders();
+
         let window: &Arc<Window> = self.window.as_ref().ok_or(DeviceError::Unexpected)?;
-        let swapchain: &SwapChain = self.swapchain.as_ref().ok_or(DeviceError::Unexpected)?;
+        let swapchain: &mut SwapChain = self.swapchain.as_mut().ok_or(DeviceError::Unexpected)?;
+        let Some(surface_texture) =
+            swapchain.acquire(&self.render_device, &mut self.present_stats)?
+        else {
+            return Ok(());
+        };
         // Create texture view
-        let surface_texture = swapchain
-            .surface()
-            .get_current_texture()
-            .expect("failed to acquire next swapchain texture");
         let texture_view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor {
                 // Without add_srgb_suffix() the image we will be working with
                 // might not be "gamma correct".
-                format: Some(swapchain.surface_format().add_srgb_suffix()),
+                format: Some(swapchain.view_format()),
                 ..Default::default()
             });
 
-        // Renders a GREEN screen
         let mut encoder = self
             .render_device
             .device()
             .create_command_encoder(&Default::default());
-        // Create the renderpass which will clear the screen.
-        let renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: None,
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &texture_view,
-                depth_slice: None,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                    r: rand::random::<f64>(),
-                    g: rand::random::<f64>(),
-                    b: rand::random::<f64>(),
-                    a: 1.0
-                    }),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
+        let target = RenderTarget {
+            view: &texture_view,
+            format: swapchain.view_format(),
+            contents_defined: false,
+        };
+        let mut clear_pass = ClearPass::new(wgpu::Color {
+            r: rand::random::<f64>(),
+            g: rand::random::<f64>(),
+            b: rand::random::<f64>(),
+            a: 1.0,
         });
-
-        // If you wanted to call any drawing commands, they would go here.
-
-        // End the renderpass.
-        drop(renderpass);
+        clear_pass
+            .record(&self.render_device, &mut encoder, &target)
+            .map_err(|err| {
+                log::error!("Failed to record {} {:?}", clear_pass.name(), err);
+                DeviceError::Unexpected
+            })?;
+        #[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+        for (_, pass) in self.shader_passes.iter_mut() {
+            if let Err(err) = pass.record(&self.render_device, &mut encoder, &target) {
+                log::error!("Failed to record {} {:?}", pass.name(), err);
+            }
+        }
 
         // Submit the command in the queue to execute
-        self.render_device
-            .command_queue()
-            .submit([encoder.finish()]);
-        window.pre_present_notify();
-        surface_texture.present();
+        self.render_device.submit([encoder.finish()]);
+        #[cfg(not(target_arch = "wasm32"))]
+        serve_capture_request(
+            &mut self.capture_request,
+            &mut self.captured_frame,
+            |swizzle_to_rgba| {
+                capture_surface(
+                    &self.render_device,
+                    swapchain,
+                    &surface_texture.texture,
+                    swizzle_to_rgba,
+                )
+            },
+        );
+        present(window, surface_texture);
+        self.present_stats.frames_presented += 1;
 
         Ok(())
     }
+
+    // Counted over the whole run, recreating the swapchain on resume keeps them.
+    pub fn present_stats(&self) -> PresentStats {
+        self.present_stats
+    }
+}
+
+// The surface image is only valid until it is presented, so it is read back right before.
+// Takes the pending request so each capture_frame() call captures a single frame.
+#[cfg(not(target_arch = "wasm32"))]
+fn serve_capture_request(
+    capture_request: &mut Option<bool>,
+    captured_frame: &mut Option<CapturedFrame>,
+    capture: impl FnOnce(bool) -> Option<CapturedFrame>,
+) {
+    if let Some(swizzle_to_rgba) = capture_request.take() {
+        *captured_frame = capture(swizzle_to_rgba);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn capture_surface(
+    device: &RenderDevice,
+    swapchain: &SwapChain,
+    texture: &wgpu::Texture,
+    swizzle_to_rgba: bool,
+) -> Option<CapturedFrame> {
+    if !swapchain
+        .surface_usage()
+        .contains(wgpu::TextureUsages::COPY_SRC)
+    {
+        log::error!(
+            "Frame capture needs COPY_SRC surface usage, configured {:?}",
+            swapchain.surface_usage()
+        );
+        return None;
+    }
+    match read_texture_pixels(device, texture, 0, "surface", swizzle_to_rgba) {
+        Ok(pixels) => Some(CapturedFrame {
+            width: texture.width(),
+            height: texture.height(),
+            format: captured_format(texture.format(), swizzle_to_rgba),
+            pixels,
+        }),
+        Err(err) => {
+            log::error!("Failed to capture frame {}", err);
+            None
+        }
+    }
+}
+
+// Byte order of the captured pixels, swizzled BGRA8 frames are laid out as RGBA8.
+#[cfg(not(target_arch = "wasm32"))]
+fn captured_format(format: wgpu::TextureFormat, swizzle_to_rgba: bool) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm if swizzle_to_rgba => wgpu::TextureFormat::Rgba8Unorm,
+        wgpu::TextureFormat::Bgra8UnormSrgb if swizzle_to_rgba => {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        }
+        format => format,
+    }
+}
+
+// pre_present_notify must precede present on every target, winit uses it to
+// pace the next redraw against the compositor.
+fn present(window: &Window, surface_texture: wgpu::SurfaceTexture) {
+    window.pre_present_notify();
+    surface_texture.present();
 }
 
 impl ApplicationHandler for Engine {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        // TODO : check the current window handle or swapchain is no more valid.
+        self.resume();
 
-        // Create window object
-        let window = Arc::new(
-            event_loop
-                .create_window(Window::default_attributes())
-                .unwrap(),
-        );
+        // Reuse the window across suspend/resume, only the surface has to be recreated.
+        let window = match &self.window {
+            Some(window) => window.clone(),
+            None => Arc::new(
+                event_loop
+                    .create_window(Window::default_attributes())
+                    .unwrap(),
+            ),
+        };
 
-        const RETRY_COUNT: u8 = 3;
-        for _ in (0..RETRY_COUNT).collect::<std::vec::Vec<u8>>() {
-            if let Ok(swapchain) = SwapChain::new(&self.render_device, window.clone()) {
+        const RETRY_COUNT: u32 = 3;
+        let swapchain = retry(RETRY_COUNT, Duration::from_millis(16), || {
+            SwapChain::new(&self.render_device, window.clone(), &self.config.swapchain)
+        });
+        match swapchain {
+            Ok(mut swapchain) => {
+                swapchain.configure_surface(
+                    &self.render_device,
+                    window.inner_size(),
+                    &mut self.present_stats,
+                );
                 self.swapchain = Some(swapchain);
-                break;
             }
-        }
-
-        if let Some(swapchain) = self.swapchain.as_mut() {
-            swapchain.configure_surface(&self.render_device, window.inner_size());
-        } else {
-            panic!("Failed to create swapchain after {:?} retry", RETRY_COUNT);
+            Err(err) => {
+                log::error!(
+                    "Failed to create swapchain after {} attempts {:?}",
+                    RETRY_COUNT,
+                    err
+                );
+                event_loop.exit();
+                return;
+            }
         }
 
         window.request_redraw();
         self.window = Some(window);
     }
 
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        self.suspend();
+    }
+
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         match event {
             WindowEvent::CloseRequested => {
@@ -123,8 +377,9 @@ impl ApplicationHandler for Engine {
             WindowEvent::RedrawRequested => {
                 match self.render() {
                     Ok(_) => {
-                        // Emits a new redraw requested event.
-                        if let Some(window) = &self.window {
+                        // Emits a new redraw requested event. On the web winit already
+                        // delivers it from requestAnimationFrame, so no extra pacing is needed.
+                        if let (false, Some(window)) = (self.suspended, &self.window) {
                             window.request_redraw();
                         }
                     }
@@ -137,10 +392,119 @@ impl ApplicationHandler for Engine {
                 // Reconfigures the size of the surface. We do not re-render
                 // here as this event is always followed up by redraw request.
                 if let Some(swapchain) = self.swapchain.as_mut() {
-                    swapchain.configure_surface(&self.render_device, size);
+                    swapchain.configure_surface(&self.render_device, size, &mut self.present_stats);
                 }
             }
             _ => (),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Engine tests need a device but no window, they are skipped without an adapter.
+    fn test_engine() -> Option<Engine> {
+        match Engine::new() {
+            Ok(engine) => Some(engine),
+            Err(err) => {
+                eprintln!("Skip engine test, no device available : {}", err);
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn render_is_noop_while_suspended() {
+        let Some(mut engine) = test_engine() else {
+            return;
+        };
+        engine.suspend();
+        assert!(engine.is_suspended());
+        assert!(engine.render().is_ok());
+        assert_eq!(engine.present_stats().frames_presented, 0);
+
+        // Once resumed render needs a window again, which the test does not have.
+        engine.resume();
+        assert!(!engine.is_suspended());
+        assert!(matches!(engine.render(), Err(DeviceError::Unexpected)));
+
+        engine.suspend();
+        assert!(engine.render().is_ok());
+    }
+
+    #[test]
+    fn missing_adapter_is_reported_as_unavailable() {
+        let mut config = EngineConfig::default();
+        config.device.backends = wgpu::Backends::empty();
+        assert!(matches!(
+            Engine::with_config(config),
+            Err(DeviceError::Unavailable(_))
+        ));
+    }
+
+    #[test]
+    fn swizzled_capture_is_reported_as_rgba() {
+        assert_eq!(
+            captured_format(wgpu::TextureFormat::Bgra8UnormSrgb, true),
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(
+            captured_format(wgpu::TextureFormat::Bgra8Unorm, false),
+            wgpu::TextureFormat::Bgra8Unorm
+        );
+        assert_eq!(
+            captured_format(wgpu::TextureFormat::Rgba16Float, true),
+            wgpu::TextureFormat::Rgba16Float
+        );
+    }
+
+    #[test]
+    fn capture_request_is_served_once() {
+        let mut capture_request = Some(true);
+        let mut captured_frame = None;
+        let mut captures = 0;
+        for _ in 0..2 {
+            serve_capture_request(&mut capture_request, &mut captured_frame, |swizzle| {
+                captures += 1;
+                Some(CapturedFrame {
+                    width: 1,
+                    height: 1,
+                    format: captured_format(wgpu::TextureFormat::Bgra8Unorm, swizzle),
+                    pixels: vec![0; 4],
+                })
+            });
+        }
+        assert_eq!(captures, 1);
+        assert!(capture_request.is_none());
+        let frame = captured_frame.unwrap();
+        assert_eq!(frame.format, wgpu::TextureFormat::Rgba8Unorm);
+    }
+
+    #[test]
+    fn capture_request_survives_failed_render() {
+        let Some(mut engine) = test_engine() else {
+            return;
+        };
+        assert!(engine.take_captured_frame().is_none());
+        engine.capture_frame(true);
+        // Without a window render() fails before the request is served.
+        assert!(engine.render().is_err());
+        assert_eq!(engine.capture_request, Some(true));
+        assert!(engine.take_captured_frame().is_none());
+    }
+
+    #[test]
+    fn present_stats_survive_suspend() {
+        let Some(mut engine) = test_engine() else {
+            return;
+        };
+        engine.present_stats.frames_presented = 3;
+        engine.present_stats.reconfigurations = 1;
+        engine.suspend();
+        engine.resume();
+        assert_eq!(engine.present_stats().frames_presented, 3);
+        assert_eq!(engine.present_stats().reconfigurations, 1);
+    }
+}
@@ -8,7 +8,7 @@ pub enum DeviceError {
     Lost,
     #[error("Unexpected error variant (driver implementation is at fault)")]
     Unexpected,
-    #[error("Current device is unavailable to run this engine")]
+    #[error("Current device is unavailable to run this engine : {0}")]
     Unavailable(String),
 }
 
@@ -16,4 +16,10 @@ pub enum DeviceError {
 pub enum ResourceError {
     #[error("Parent resource already dropped")]
     Orphan,
+    #[error("Invalid resource create info : {0}")]
+    InvalidCreateInfo(String),
+    #[error("Failed to map buffer : {0}")]
+    MapFailed(String),
+    #[error("Failed to load shader : {0}")]
+    ShaderLoadFailed(String),
 }
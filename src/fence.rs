@@ -0,0 +1,76 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{error::DeviceError, render_device::RenderDevice};
+
+// Tracks a single queue submission so readbacks wait on exactly the work that produced them.
+pub struct GpuFence {
+    pub(crate) submission_index: wgpu::SubmissionIndex,
+}
+
+impl GpuFence {
+    pub fn new(submission_index: wgpu::SubmissionIndex) -> GpuFence {
+        GpuFence { submission_index }
+    }
+
+    pub fn submission_index(&self) -> &wgpu::SubmissionIndex {
+        &self.submission_index
+    }
+
+    // Blocks until the GPU finished the submission. Not available on the web, where the
+    // browser resolves submissions asynchronously.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wait(&self, device: &RenderDevice) -> Result<(), DeviceError> {
+        device
+            .device()
+            .poll(wgpu::PollType::Wait {
+                submission_index: Some(self.submission_index.clone()),
+                timeout: None,
+            })
+            .map(|_| ())
+            .map_err(|err| {
+                log::error!("Failed to wait gpu fence {:?}", err);
+                DeviceError::Unexpected
+            })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::buffer::Buffer;
+    use crate::render_device::test_device;
+    use crate::render_resource::BufferCreateInfo;
+
+    #[test]
+    fn wait_covers_submitted_copy() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let source = Buffer::new(
+            &device,
+            BufferCreateInfo {
+                size: 16,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            },
+            "copy_source",
+        );
+        let destination = Buffer::new(
+            &device,
+            BufferCreateInfo {
+                size: 16,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            },
+            "copy_destination",
+        );
+        let data: Vec<u8> = (0..16).rev().collect();
+        device
+            .command_queue()
+            .write_buffer(&source.buffer, 0, &data);
+
+        let mut encoder = device.device().create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(&source.buffer, 0, &destination.buffer, 0, 16);
+        let fence = device.submit([encoder.finish()]);
+        fence.wait(&device).unwrap();
+
+        let copied = pollster::block_on(destination.map_read_async_after(&device, &fence));
+        assert_eq!(copied.unwrap(), data);
+    }
+}
@@ -0,0 +1,15 @@
+pub mod buffer;
+pub mod common;
+pub mod engine;
+pub mod error;
+pub mod fence;
+pub mod mipmap;
+pub mod render_device;
+pub mod render_pass;
+pub mod render_resource;
+pub mod resource_registry;
+pub mod sampler;
+#[cfg(all(feature = "shader-hot-reload", not(target_arch = "wasm32")))]
+pub mod shader_watcher;
+pub mod swapchain;
+pub mod texture;
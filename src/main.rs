@@ -1,14 +1,32 @@
+use p0::engine::{Engine, EngineConfig};
 use winit::event_loop::{ControlFlow, EventLoop};
-mod engine;
-mod error;
-mod render_device;
-mod render_resource;
-mod swapchain;
-mod texture;
-use engine::Engine;
+
+// Builds the native logger from the engine config. RUST_LOG is parsed last so it
+// keeps overriding the programmatic levels.
+#[cfg(not(target_arch = "wasm32"))]
+fn logger_builder(config: &EngineConfig) -> env_logger::Builder {
+    let mut builder = env_logger::builder();
+    builder.filter_level(config.log_level);
+    for (module, level) in &config.module_log_levels {
+        builder.filter_module(module, *level);
+    }
+    builder.parse_default_env();
+    builder
+}
+
+// Returns the value of `key` in a `?a=b&c=d` style query string.
+#[cfg(target_arch = "wasm32")]
+fn parse_url_query_string<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
 
 // Initialize logging in platform dependant ways.
-fn init_logger() {
+fn init_logger(config: &EngineConfig) {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             // As we don't have an environment to pull logging level from, we use the query string.
@@ -16,37 +34,26 @@ fn init_logger() {
             let query_level: Option<log::LevelFilter> = parse_url_query_string(&query_string, "RUST_LOG")
                 .and_then(|x| x.parse().ok());
 
-            // We keep wgpu at Error level, as it's very noisy.
-            let base_level = query_level.unwrap_or(log::LevelFilter::Info);
-            let wgpu_level = query_level.unwrap_or(log::LevelFilter::Error);
+            let mut dispatch = fern::Dispatch::new().level(query_level.unwrap_or(config.log_level));
+            for (module, level) in &config.module_log_levels {
+                dispatch = dispatch.level_for(module.clone(), query_level.unwrap_or(*level));
+            }
 
             // On web, we use fern, as console_log doesn't have filtering on a per-module level.
-            fern::Dispatch::new()
-                .level(base_level)
-                .level_for("wgpu_core", wgpu_level)
-                .level_for("wgpu_hal", wgpu_level)
-                .level_for("naga", wgpu_level)
+            dispatch
                 .chain(fern::Output::call(console_log::log))
                 .apply()
                 .unwrap();
             std::panic::set_hook(Box::new(console_error_panic_hook::hook));
         } else {
-            // parse_default_env will read the RUST_LOG environment variable and apply it on top
-            // of these default filters.
-            env_logger::builder()
-                .filter_level(log::LevelFilter::Info)
-                // We keep wgpu at Error level, as it's very noisy.
-                .filter_module("wgpu_core", log::LevelFilter::Info)
-                .filter_module("wgpu_hal", log::LevelFilter::Error)
-                .filter_module("naga", log::LevelFilter::Error)
-                .parse_default_env()
-                .init();
+            logger_builder(config).init();
         }
     }
 }
 
 fn main() {
-    init_logger();
+    let config = EngineConfig::default();
+    init_logger(&config);
 
     log::info!(
         "Enabled backends: {:?}",
@@ -67,11 +74,64 @@ fn main() {
     // the background.
     // event_loop.set_control_flow(ControlFlow::Wait);
 
-    let mut engine = Engine::new()
-        .map_err(|err| {
-            log::error!("Failed to initialize p0 engine {:?}", err);
-            panic!("fatal exit");
-        })
-        .unwrap();
-    event_loop.run_app(&mut engine).unwrap();
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            use winit::platform::web::EventLoopExtWebSys;
+
+            wasm_bindgen_futures::spawn_local(async move {
+                match Engine::new_async(config).await {
+                    Ok(engine) => event_loop.spawn_app(engine),
+                    Err(err) => log::error!("Failed to initialize p0 engine : {}", err),
+                }
+            });
+        } else {
+            let mut engine = match Engine::with_config(config) {
+                Ok(engine) => engine,
+                Err(err) => {
+                    log::error!("Failed to initialize p0 engine : {}", err);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(err) = event_loop.run_app(&mut engine) {
+                log::error!("Event loop terminated with error : {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn enabled(logger: &env_logger::Logger, target: &str, level: log::Level) -> bool {
+        log::Log::enabled(
+            logger,
+            &log::Metadata::builder().target(target).level(level).build(),
+        )
+    }
+
+    #[test]
+    fn logger_builder_applies_config_levels() {
+        // RUST_LOG overrides the config on purpose, the test only covers the config levels.
+        if std::env::var_os("RUST_LOG").is_some() {
+            return;
+        }
+        let config = EngineConfig {
+            log_level: log::LevelFilter::Warn,
+            module_log_levels: vec![("p0::render".to_string(), log::LevelFilter::Trace)],
+            ..Default::default()
+        };
+        let logger = logger_builder(&config).build();
+
+        assert!(enabled(&logger, "p0", log::Level::Warn));
+        assert!(!enabled(&logger, "p0", log::Level::Info));
+        assert!(enabled(&logger, "p0::render", log::Level::Trace));
+
+        // wgpu is noisy, the defaults only let its errors through on every target.
+        let logger = logger_builder(&EngineConfig::default()).build();
+        assert!(enabled(&logger, "p0", log::Level::Info));
+        assert!(!enabled(&logger, "wgpu_core", log::Level::Info));
+        assert!(enabled(&logger, "wgpu_core", log::Level::Error));
+    }
 }
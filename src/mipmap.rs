@@ -0,0 +1,304 @@
+use std::sync::Arc;
+
+use crate::error::ResourceError;
+use crate::render_device::RenderDevice;
+use crate::render_pass::{FullscreenPass, RenderPass, RenderTarget};
+use crate::render_resource::{ResourceFlag, SamplerCreateInfo, TextureViewCreateInfo};
+use crate::sampler::Sampler;
+use crate::texture::{Texture, TextureView};
+
+const BLIT_FRAGMENT_SHADER: &str = r#"
+@group(0) @binding(0) var source_mip: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source_mip, source_sampler, in.uv);
+}
+"#;
+
+// {STORAGE_FORMAT} is replaced with the WGSL name of the texture format.
+const DOWNSAMPLE_COMPUTE_SHADER: &str = r#"
+@group(0) @binding(0) var source_mip: texture_2d<f32>;
+@group(0) @binding(1) var target_mip: texture_storage_2d<{STORAGE_FORMAT}, write>;
+
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let target_size = textureDimensions(target_mip);
+    if (id.x >= target_size.x || id.y >= target_size.y) {
+        return;
+    }
+    // Average the 2x2 footprint, clamped for odd sized source mips.
+    let max_coord = textureDimensions(source_mip) - vec2<u32>(1u, 1u);
+    let base = id.xy * 2u;
+    let sum = textureLoad(source_mip, min(base, max_coord), 0)
+        + textureLoad(source_mip, min(base + vec2<u32>(1u, 0u), max_coord), 0)
+        + textureLoad(source_mip, min(base + vec2<u32>(0u, 1u), max_coord), 0)
+        + textureLoad(source_mip, min(base + vec2<u32>(1u, 1u), max_coord), 0);
+    textureStore(target_mip, id.xy, sum * 0.25);
+}
+"#;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+// Which implementation generate_mipmaps_compute() ended up running.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MipmapPath {
+    Blit,
+    Compute,
+}
+
+// WGSL storage format name for formats the downsample shader can write.
+fn storage_format_name(format: wgpu::TextureFormat) -> Option<&'static str> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm => Some("rgba8unorm"),
+        wgpu::TextureFormat::Rgba8Snorm => Some("rgba8snorm"),
+        wgpu::TextureFormat::Rgba16Float => Some("rgba16float"),
+        wgpu::TextureFormat::Rgba32Float => Some("rgba32float"),
+        wgpu::TextureFormat::Rg32Float => Some("rg32float"),
+        wgpu::TextureFormat::R32Float => Some("r32float"),
+        _ => None,
+    }
+}
+
+pub fn is_storage_format_supported(device: &RenderDevice, format: wgpu::TextureFormat) -> bool {
+    storage_format_name(format).is_some()
+        && device
+            .adapter()
+            .get_texture_format_features(format)
+            .allowed_usages
+            .contains(wgpu::TextureUsages::STORAGE_BINDING)
+}
+
+impl Texture {
+    fn mip_view(self: &Arc<Self>, mip: u32, slice: u32) -> Arc<TextureView> {
+        self.create_view(TextureViewCreateInfo {
+            base_mip: mip,
+            num_mips: 1,
+            base_slice: slice,
+            num_slices: 1,
+            dimension: Some(wgpu::TextureViewDimension::D2),
+        })
+    }
+
+    // Fills every mip from mip 0 by rendering each level from the previous one with a
+    // linear sampler. One pass is reused for every level so the pipeline is built once.
+    pub fn generate_mipmaps(self: &Arc<Self>, device: &RenderDevice) -> Result<(), ResourceError> {
+        if self.texture.mip_level_count() > 1
+            && !self
+                .texture
+                .usage()
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        {
+            return Err(ResourceError::InvalidCreateInfo(format!(
+                "{} format {:?} can't be rendered to for mipmap generation",
+                self.info.name, self.info.format
+            )));
+        }
+        let sampler = Sampler::new(
+            device,
+            SamplerCreateInfo::default(),
+            &format!("{} mip sampler", self.info.name),
+        )?;
+
+        let mut blit_pass = FullscreenPass::new(
+            BLIT_FRAGMENT_SHADER,
+            &format!("{} mip blit", self.info.name),
+        );
+        let mut encoder = device.device().create_command_encoder(&Default::default());
+        for slice in 0..self.info.num_slices {
+            for mip in 1..self.texture.mip_level_count() {
+                let target_view = self.mip_view(mip, slice);
+                blit_pass.set_inputs(vec![(self.mip_view(mip - 1, slice), sampler.clone())]);
+                blit_pass.record(
+                    device,
+                    &mut encoder,
+                    &RenderTarget {
+                        view: &target_view.view,
+                        format: self.info.format,
+                        contents_defined: false,
+                    },
+                )?;
+            }
+        }
+        device.submit([encoder.finish()]);
+        Ok(())
+    }
+
+    // Same result as generate_mipmaps() without render pass state, each level is
+    // downsampled by a compute shader writing a storage texture. Falls back to the blit
+    // path when the format can't be bound as storage, the returned path tells which ran.
+    pub fn generate_mipmaps_compute(
+        self: &Arc<Self>,
+        device: &RenderDevice,
+    ) -> Result<MipmapPath, ResourceError> {
+        // GL emulates views by clamping the mip range of the whole texture, so storing to
+        // one mip while sampling another of the same texture silently drops the writes.
+        if device.adapter().get_info().backend == wgpu::Backend::Gl {
+            log::debug!(
+                "{} can't read and write its own mips on GL, fall back to blit mipmap generation",
+                self.info.name
+            );
+            return self.generate_mipmaps(device).map(|_| MipmapPath::Blit);
+        }
+        let storage_format = storage_format_name(self.info.format)
+            .filter(|_| self.info.base_info.flags.contains(ResourceFlag::ALLOW_UAV));
+        let Some(storage_format) = storage_format else {
+            log::debug!(
+                "{} format {:?} can't be written as storage, fall back to blit mipmap generation",
+                self.info.name,
+                self.info.format
+            );
+            return self.generate_mipmaps(device).map(|_| MipmapPath::Blit);
+        };
+
+        let label = format!("{} mip downsample", self.info.name);
+        let wgpu_device: &wgpu::Device = device.device();
+        let module = wgpu_device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&label),
+            source: wgpu::ShaderSource::Wgsl(
+                DOWNSAMPLE_COMPUTE_SHADER
+                    .replace("{STORAGE_FORMAT}", storage_format)
+                    .into(),
+            ),
+        });
+        let pipeline = wgpu_device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&label),
+            layout: None,
+            module: &module,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+        let mut encoder = wgpu_device.create_command_encoder(&Default::default());
+        for slice in 0..self.info.num_slices {
+            for mip in 1..self.texture.mip_level_count() {
+                let source_view = self.mip_view(mip - 1, slice);
+                let target_view = self.mip_view(mip, slice);
+                let bind_group = wgpu_device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(&label),
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&source_view.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&target_view.view),
+                        },
+                    ],
+                });
+
+                let width = (self.info.extent.width >> mip).max(1);
+                let height = (self.info.extent.height >> mip).max(1);
+                let mut computepass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some(&label),
+                    timestamp_writes: None,
+                });
+                computepass.set_pipeline(&pipeline);
+                computepass.set_bind_group(0, &bind_group, &[]);
+                computepass.dispatch_workgroups(
+                    width.div_ceil(WORKGROUP_SIZE),
+                    height.div_ceil(WORKGROUP_SIZE),
+                    1,
+                );
+            }
+        }
+        device.submit([encoder.finish()]);
+        Ok(MipmapPath::Compute)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_device::{test_device, test_texture};
+
+    const SIZE: u32 = 4;
+
+    fn source_texture(device: &RenderDevice, name: &str) -> Arc<Texture> {
+        let texture = test_texture(
+            device,
+            wgpu::TextureFormat::Rgba8Unorm,
+            3,
+            1,
+            ResourceFlag::ALLOW_UAV,
+            name,
+        );
+
+        let pixels: Vec<u8> = (0..SIZE * SIZE)
+            .flat_map(|index| {
+                let (x, y) = (index % SIZE, index / SIZE);
+                [(x * 64) as u8, (y * 64) as u8, ((x ^ y) * 32) as u8, 255]
+            })
+            .collect();
+        device.command_queue().write_texture(
+            texture.texture.as_image_copy(),
+            &pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(SIZE * 4),
+                rows_per_image: Some(SIZE),
+            },
+            texture.texture.size(),
+        );
+        texture
+    }
+
+    #[test]
+    fn compute_mipmaps_report_blit_fallback() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        // Without ALLOW_UAV the texture can't be written as storage on any backend.
+        let texture = test_texture(
+            &device,
+            wgpu::TextureFormat::Rgba8Unorm,
+            3,
+            1,
+            ResourceFlag::NONE,
+            "blit_fallback",
+        );
+        assert_eq!(
+            texture.generate_mipmaps_compute(&device).unwrap(),
+            MipmapPath::Blit
+        );
+    }
+
+    #[test]
+    fn blit_and_compute_mipmaps_match() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        if device.adapter().get_info().backend == wgpu::Backend::Gl {
+            eprintln!("Skip compute mipmap test, GL always falls back to blit");
+            return;
+        }
+        let blit = source_texture(&device, "blit_mips");
+        blit.generate_mipmaps(&device).unwrap();
+        let compute = source_texture(&device, "compute_mips");
+        assert_eq!(
+            compute.generate_mipmaps_compute(&device).unwrap(),
+            MipmapPath::Compute
+        );
+
+        for mip in 1..blit.texture.mip_level_count() {
+            let blit_pixels = blit.read_mip_pixels(&device, mip, false).unwrap();
+            let compute_pixels = compute.read_mip_pixels(&device, mip, false).unwrap();
+            assert_eq!(blit_pixels.len(), compute_pixels.len());
+            // Filtering precision differs between the sampler and the shader average.
+            for (blit_value, compute_value) in blit_pixels.iter().zip(&compute_pixels) {
+                assert!(
+                    blit_value.abs_diff(*compute_value) <= 2,
+                    "mip {} blit {:?} compute {:?}",
+                    mip,
+                    blit_pixels,
+                    compute_pixels
+                );
+            }
+        }
+    }
+}
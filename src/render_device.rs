@@ -1,4 +1,30 @@
 use crate::error::DeviceError;
+use crate::fence::GpuFence;
+
+// Snapshot of the backend allocator state. Compare against the sum of
+// ResourceInfo::allocation_size to spot untracked allocations.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemoryReport {
+    pub num_allocations: usize,
+    pub num_blocks: usize,
+    pub allocated_bytes: u64,
+    pub reserved_bytes: u64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RenderDeviceConfig {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+}
+
+impl Default for RenderDeviceConfig {
+    fn default() -> Self {
+        RenderDeviceConfig {
+            backends: wgpu::InstanceDescriptor::default().backends,
+            power_preference: wgpu::PowerPreference::default(),
+        }
+    }
+}
 
 pub struct RenderDevice {
     pub(crate) instance: wgpu::Instance,
@@ -9,22 +35,72 @@ pub struct RenderDevice {
 
 impl RenderDevice {
     pub async fn new() -> Result<RenderDevice, DeviceError> {
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions::default())
+        RenderDevice::with_config(RenderDeviceConfig::default()).await
+    }
+
+    pub async fn with_config(config: RenderDeviceConfig) -> Result<RenderDevice, DeviceError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: config.backends,
+            ..Default::default()
+        });
+
+        // Adapters can only be enumerated up front on native targets. An empty list means
+        // no driver for the requested backends exists, which is distinct from a request failure.
+        #[cfg(not(target_arch = "wasm32"))]
+        let adapters = {
+            let mut adapters = instance.enumerate_adapters(config.backends);
+            if adapters.is_empty() {
+                return Err(DeviceError::Unavailable(format!(
+                    "no compatible GPU adapter found for backends {:?}",
+                    config.backends
+                )));
+            }
+            // Stable sort keeps the driver order among adapters of equal preference.
+            adapters.sort_by_key(|adapter| {
+                adapter_priority(&adapter.get_info(), config.power_preference)
+            });
+            adapters
+        };
+        #[cfg(target_arch = "wasm32")]
+        let adapters = vec![instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: config.power_preference,
+                ..Default::default()
+            })
             .await
             .map_err(|err| {
                 DeviceError::Unavailable(format!(
-                    "Failed to get adapter from current device {:?}",
-                    err
+                    "Failed to get adapter for backends {:?} from current device {:?}",
+                    config.backends, err
                 ))
-            })?;
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default())
-            .await
-            .map_err(|err| {
-                DeviceError::Unavailable(format!("Failed to create logical device {:?}", err))
-            })?;
+            })?];
+
+        // A flaky driver on the preferred adapter should not take the whole engine down,
+        // so fall through to the next candidate until one hands out a device.
+        let candidates = adapters.into_iter().map(|adapter| {
+            let info = adapter.get_info();
+            let name = format!("{} ({:?})", info.name, info.backend);
+            (name, move || async move {
+                let (device, queue) = adapter
+                    .request_device(&wgpu::DeviceDescriptor::default())
+                    .await?;
+                Ok::<_, wgpu::RequestDeviceError>((adapter, device, queue))
+            })
+        });
+        let (adapter, device, queue) = first_successful_candidate(candidates).await?;
+
+        let info = adapter.get_info();
+        log::info!(
+            "Logical device created on {} ({:?})",
+            info.name,
+            info.backend
+        );
+        if is_software_adapter_info(&info) {
+            log::warn!(
+                "{} is a software rasterizer, expect low frame rates",
+                info.name
+            );
+        }
         Ok(RenderDevice {
             instance,
             adapter,
@@ -41,6 +117,11 @@ impl RenderDevice {
         &self.adapter
     }
 
+    // True when rendering falls back to a CPU rasterizer such as llvmpipe or WARP.
+    pub fn is_software_adapter(&self) -> bool {
+        is_software_adapter_info(&self.adapter.get_info())
+    }
+
     pub fn device(&self) -> &wgpu::Device {
         &self.device
     }
@@ -48,4 +129,307 @@ impl RenderDevice {
     pub fn command_queue(&self) -> &wgpu::Queue {
         &self.queue
     }
+
+    // Submits the command buffers and returns a fence signaled when they finished on the GPU.
+    pub fn submit(
+        &self,
+        command_buffers: impl IntoIterator<Item = wgpu::CommandBuffer>,
+    ) -> GpuFence {
+        GpuFence::new(self.queue.submit(command_buffers))
+    }
+
+    // Runs `f` inside a validation error scope and returns the first error it raised,
+    // instead of letting it reach the uncaptured error handler. Errors cannot be awaited
+    // synchronously on the web, so they stay uncaptured there.
+    pub fn validation_scope<T>(
+        &self,
+        f: impl FnOnce(&wgpu::Device) -> T,
+    ) -> Result<T, wgpu::Error> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                Ok(f(&self.device))
+            } else {
+                self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+                let result = f(&self.device);
+                match pollster::block_on(self.device.pop_error_scope()) {
+                    Some(err) => Err(err),
+                    None => Ok(result),
+                }
+            }
+        }
+    }
+
+    // Runs `create` and returns the bytes the backend allocator grew by meanwhile. Falls back
+    // to `estimate` when the backend does not expose its allocator or nothing new showed up
+    // (e.g. the resource reused a freed range). Resources created concurrently on other
+    // threads are counted too, so treat the result as best-effort.
+    pub fn measure_allocation<T>(&self, estimate: u64, create: impl FnOnce() -> T) -> (T, u64) {
+        let allocated_bytes = || {
+            self.device
+                .generate_allocator_report()
+                .map(|report| report.total_allocated_bytes)
+        };
+        let before = allocated_bytes();
+        let resource = create();
+        let size = measured_allocation_size(before, allocated_bytes(), estimate);
+        (resource, size)
+    }
+
+    // Returns None when the backend does not expose its allocator (e.g. GL, WebGPU).
+    pub fn memory_report(&self) -> Option<MemoryReport> {
+        let report = self.device.generate_allocator_report()?;
+        Some(MemoryReport {
+            num_allocations: report.allocations.len(),
+            num_blocks: report.blocks.len(),
+            allocated_bytes: report.total_allocated_bytes,
+            reserved_bytes: report.total_reserved_bytes,
+        })
+    }
+}
+
+fn measured_allocation_size(before: Option<u64>, after: Option<u64>, estimate: u64) -> u64 {
+    match (before, after) {
+        (Some(before), Some(after)) if after > before => after - before,
+        _ => estimate,
+    }
+}
+
+// GPU backed tests call this and return early when the machine has no usable adapter.
+#[cfg(test)]
+pub(crate) fn test_device() -> Option<RenderDevice> {
+    match pollster::block_on(RenderDevice::new()) {
+        Ok(device) => Some(device),
+        Err(err) => {
+            eprintln!("Skip GPU test, no device available : {}", err);
+            None
+        }
+    }
+}
+
+// 4x4 texture description shared by the tests of the resource modules.
+#[cfg(test)]
+pub(crate) fn test_texture_create_info(
+    format: wgpu::TextureFormat,
+    num_mips: u32,
+    num_slices: u32,
+    flags: crate::render_resource::ResourceFlag,
+) -> crate::render_resource::TextureCreateInfo {
+    crate::render_resource::TextureCreateInfo {
+        extent: wgpu::Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        },
+        format,
+        num_mips,
+        num_slices,
+        flags,
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_texture(
+    device: &RenderDevice,
+    format: wgpu::TextureFormat,
+    num_mips: u32,
+    num_slices: u32,
+    flags: crate::render_resource::ResourceFlag,
+    name: &str,
+) -> std::sync::Arc<crate::texture::Texture> {
+    let create_info = test_texture_create_info(format, num_mips, num_slices, flags);
+    crate::texture::Texture::new(device, create_info, name)
+}
+
+fn is_software_adapter_info(info: &wgpu::AdapterInfo) -> bool {
+    // The noop backend never touches a GPU either.
+    info.device_type == wgpu::DeviceType::Cpu || info.backend == wgpu::Backend::Noop
+}
+
+// Awaits each candidate in order and returns the first success. Failures are logged and
+// aggregated into the error when no candidate succeeds.
+async fn first_successful_candidate<T, E, F, Fut>(
+    candidates: impl IntoIterator<Item = (String, F)>,
+) -> Result<T, DeviceError>
+where
+    E: std::fmt::Debug,
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+{
+    let mut failures = Vec::new();
+    for (name, candidate) in candidates {
+        match candidate().await {
+            Ok(value) => return Ok(value),
+            Err(err) => {
+                log::warn!("Failed to create logical device on {} {:?}", name, err);
+                failures.push(format!("{} : {:?}", name, err));
+            }
+        }
+    }
+    Err(DeviceError::Unavailable(format!(
+        "Failed to create logical device on every adapter [{}]",
+        failures.join(", ")
+    )))
+}
+
+// Lower value is tried first. Software rasterizers always come last, the power preference
+// only orders the hardware types, and GL is kept as a fallback behind the native APIs.
+#[cfg(not(target_arch = "wasm32"))]
+fn adapter_priority(info: &wgpu::AdapterInfo, power_preference: wgpu::PowerPreference) -> (u8, u8) {
+    let (first, second) = match power_preference {
+        wgpu::PowerPreference::LowPower => (
+            wgpu::DeviceType::IntegratedGpu,
+            wgpu::DeviceType::DiscreteGpu,
+        ),
+        _ => (
+            wgpu::DeviceType::DiscreteGpu,
+            wgpu::DeviceType::IntegratedGpu,
+        ),
+    };
+    let device_rank = match info.device_type {
+        wgpu::DeviceType::Cpu => 3,
+        device_type if device_type == first => 0,
+        device_type if device_type == second => 1,
+        _ => 2,
+    };
+    let backend_rank = match info.backend {
+        wgpu::Backend::Gl => 1,
+        _ => 0,
+    };
+    (device_rank, backend_rank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapter_info(device_type: wgpu::DeviceType, backend: wgpu::Backend) -> wgpu::AdapterInfo {
+        wgpu::AdapterInfo {
+            name: format!("{:?} {:?}", device_type, backend),
+            vendor: 0,
+            device: 0,
+            device_type,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend,
+        }
+    }
+
+    #[test]
+    fn software_adapters_are_ranked_last() {
+        let cpu = adapter_info(wgpu::DeviceType::Cpu, wgpu::Backend::Vulkan);
+        let virtual_gpu = adapter_info(wgpu::DeviceType::VirtualGpu, wgpu::Backend::Vulkan);
+        let integrated_gl = adapter_info(wgpu::DeviceType::IntegratedGpu, wgpu::Backend::Gl);
+        let integrated = adapter_info(wgpu::DeviceType::IntegratedGpu, wgpu::Backend::Vulkan);
+        let discrete = adapter_info(wgpu::DeviceType::DiscreteGpu, wgpu::Backend::Vulkan);
+
+        for power_preference in [
+            wgpu::PowerPreference::None,
+            wgpu::PowerPreference::LowPower,
+            wgpu::PowerPreference::HighPerformance,
+        ] {
+            let cpu_rank = adapter_priority(&cpu, power_preference);
+            for gpu in [&virtual_gpu, &integrated_gl, &integrated, &discrete] {
+                assert!(adapter_priority(gpu, power_preference) < cpu_rank);
+            }
+            assert!(
+                adapter_priority(&integrated, power_preference)
+                    < adapter_priority(&integrated_gl, power_preference)
+            );
+        }
+
+        let high = wgpu::PowerPreference::HighPerformance;
+        assert!(adapter_priority(&discrete, high) < adapter_priority(&integrated, high));
+        let low = wgpu::PowerPreference::LowPower;
+        assert!(adapter_priority(&integrated, low) < adapter_priority(&discrete, low));
+    }
+
+    #[test]
+    fn candidates_fall_through_to_first_success() {
+        let candidates = [("first", Err("lost")), ("second", Ok(2)), ("third", Ok(3))]
+            .into_iter()
+            .map(|(name, result)| (name.to_string(), move || std::future::ready(result)));
+        assert_eq!(
+            pollster::block_on(first_successful_candidate(candidates)).unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn candidate_failures_are_aggregated() {
+        let candidates = [("first", Err::<u32, _>("lost")), ("second", Err("timeout"))]
+            .into_iter()
+            .map(|(name, result)| (name.to_string(), move || std::future::ready(result)));
+        match pollster::block_on(first_successful_candidate(candidates)) {
+            Err(DeviceError::Unavailable(message)) => {
+                assert!(message.contains("first : \"lost\""));
+                assert!(message.contains("second : \"timeout\""));
+            }
+            _ => panic!("expected every candidate to fail"),
+        }
+    }
+
+    #[test]
+    fn empty_backends_report_missing_adapter() {
+        let config = RenderDeviceConfig {
+            backends: wgpu::Backends::empty(),
+            ..Default::default()
+        };
+        match pollster::block_on(RenderDevice::with_config(config)) {
+            Err(DeviceError::Unavailable(message)) => {
+                assert!(message.contains("no compatible GPU adapter found for backends"))
+            }
+            Err(err) => panic!("unexpected error : {}", err),
+            Ok(_) => panic!("a device was created without any backend"),
+        }
+    }
+
+    #[test]
+    fn software_adapter_matches_adapter_info() {
+        assert!(is_software_adapter_info(&adapter_info(
+            wgpu::DeviceType::Cpu,
+            wgpu::Backend::Vulkan
+        )));
+        assert!(is_software_adapter_info(&adapter_info(
+            wgpu::DeviceType::Other,
+            wgpu::Backend::Noop
+        )));
+        assert!(!is_software_adapter_info(&adapter_info(
+            wgpu::DeviceType::DiscreteGpu,
+            wgpu::Backend::Vulkan
+        )));
+
+        let Some(device) = test_device() else {
+            return;
+        };
+        let info = device.adapter().get_info();
+        assert_eq!(
+            device.is_software_adapter(),
+            info.device_type == wgpu::DeviceType::Cpu || info.backend == wgpu::Backend::Noop
+        );
+    }
+
+    #[test]
+    fn measured_allocation_falls_back_to_estimate() {
+        assert_eq!(measured_allocation_size(Some(100), Some(356), 64), 256);
+        assert_eq!(measured_allocation_size(None, None, 64), 64);
+        // Nothing new was allocated, e.g. a freed range got reused.
+        assert_eq!(measured_allocation_size(Some(100), Some(100), 64), 64);
+        assert_eq!(measured_allocation_size(Some(100), Some(50), 64), 64);
+    }
+
+    #[test]
+    fn memory_report_follows_backend_support() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let report = device.memory_report();
+        // Only the DX12 hal implements allocator reports.
+        assert_eq!(
+            report.is_some(),
+            device.adapter().get_info().backend == wgpu::Backend::Dx12
+        );
+        if let Some(report) = report {
+            assert!(report.allocated_bytes <= report.reserved_bytes);
+        }
+    }
 }
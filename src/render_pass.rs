@@ -0,0 +1,794 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::buffer::Buffer;
+use crate::error::ResourceError;
+use crate::render_device::RenderDevice;
+use crate::sampler::Sampler;
+use crate::texture::TextureView;
+
+// Color attachment a pass renders into. Swapchain images are not our Texture type,
+// so the raw view and its format are passed instead.
+pub struct RenderTarget<'a> {
+    pub view: &'a wgpu::TextureView,
+    pub format: wgpu::TextureFormat,
+    // Whether the previous contents are meaningful. Freshly acquired swapchain images and
+    // never written textures are undefined, so LoadOp::Load is rejected on them.
+    pub contents_defined: bool,
+}
+
+// Validates the color attachment operations of a pass against its target.
+fn color_operations(
+    pass_name: &str,
+    ops: wgpu::Operations<wgpu::Color>,
+    target: &RenderTarget,
+) -> Result<wgpu::Operations<wgpu::Color>, ResourceError> {
+    if matches!(ops.load, wgpu::LoadOp::Load) && !target.contents_defined {
+        return Err(ResourceError::InvalidCreateInfo(format!(
+            "{} loads a target whose previous contents are undefined",
+            pass_name
+        )));
+    }
+    Ok(ops)
+}
+
+pub trait RenderPass {
+    fn name(&self) -> &str;
+    fn record(
+        &mut self,
+        device: &RenderDevice,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &RenderTarget,
+    ) -> Result<(), ResourceError>;
+}
+
+pub struct ClearPass {
+    pub color_ops: wgpu::Operations<wgpu::Color>,
+}
+
+impl ClearPass {
+    pub fn new(color: wgpu::Color) -> ClearPass {
+        ClearPass {
+            color_ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(color),
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    pub fn with_color_ops(mut self, color_ops: wgpu::Operations<wgpu::Color>) -> ClearPass {
+        self.color_ops = color_ops;
+        self
+    }
+}
+
+impl RenderPass for ClearPass {
+    fn name(&self) -> &str {
+        "clear_pass"
+    }
+
+    fn record(
+        &mut self,
+        _device: &RenderDevice,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &RenderTarget,
+    ) -> Result<(), ResourceError> {
+        let ops = color_operations(self.name(), self.color_ops, target)?;
+        // Nothing is drawn, beginning and ending the pass is enough to clear the target.
+        let renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(self.name()),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.view,
+                depth_slice: None,
+                resolve_target: None,
+                ops,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        drop(renderpass);
+        Ok(())
+    }
+}
+
+const FULLSCREEN_VERTEX_SHADER: &str = r#"
+struct FullscreenVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> FullscreenVertexOutput {
+    // A single triangle covering the whole clip space, uv spans [0, 1] over the visible area.
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: FullscreenVertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+"#;
+
+struct FullscreenPipeline {
+    format: wgpu::TextureFormat,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // Rebuilt on the next record after set_inputs() swapped the inputs.
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+// Draws a fullscreen triangle shaded by a user supplied WGSL fragment shader.
+// The shader must define `fs_main` taking `FullscreenVertexOutput` (uv at location 0).
+// Each input is bound to group 0 as texture at binding 2 * i and sampler at 2 * i + 1.
+pub struct FullscreenPass {
+    name: String,
+    fragment_shader: String,
+    inputs: Vec<(Arc<TextureView>, Arc<Sampler>)>,
+    color_ops: wgpu::Operations<wgpu::Color>,
+    // Compiled on first record and rebuilt only when the target format changes.
+    pipeline: Option<FullscreenPipeline>,
+}
+
+impl FullscreenPass {
+    pub fn new(fragment_shader: &str, name: &str) -> FullscreenPass {
+        FullscreenPass {
+            name: name.to_string(),
+            fragment_shader: fragment_shader.to_string(),
+            inputs: Vec::new(),
+            color_ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+            pipeline: None,
+        }
+    }
+
+    pub fn with_color_ops(mut self, color_ops: wgpu::Operations<wgpu::Color>) -> FullscreenPass {
+        self.color_ops = color_ops;
+        self
+    }
+
+    pub fn with_input(mut self, view: Arc<TextureView>, sampler: Arc<Sampler>) -> FullscreenPass {
+        self.inputs.push((view, sampler));
+        self.pipeline = None;
+        self
+    }
+
+    // Replaces every input. The pipeline is kept when the input count is unchanged, only
+    // the bind group is rebuilt, so one pass can be re-recorded over different textures.
+    pub fn set_inputs(&mut self, inputs: Vec<(Arc<TextureView>, Arc<Sampler>)>) {
+        if inputs.len() != self.inputs.len() {
+            self.pipeline = None;
+        } else if let Some(pipeline) = self.pipeline.as_mut() {
+            pipeline.bind_group = None;
+        }
+        self.inputs = inputs;
+    }
+
+    // Validates the new fragment shader and swaps it in. When a pipeline is already cached
+    // it is rebuilt right away so entry point and interface errors are caught here too.
+    // On error the previous shader and pipeline are kept.
+    pub fn reload(
+        &mut self,
+        device: &RenderDevice,
+        fragment_shader: &str,
+    ) -> Result<(), ResourceError> {
+        match self.pipeline.as_ref().map(|cached| cached.format) {
+            Some(format) => {
+                let pipeline = self.compile(device, fragment_shader, format)?;
+                self.pipeline = Some(pipeline);
+            }
+            None => {
+                device
+                    .validation_scope(|device| {
+                        self.create_shader_module(device, fragment_shader);
+                    })
+                    .map_err(|err| {
+                        ResourceError::ShaderLoadFailed(format!("{} {}", self.name, err))
+                    })?;
+            }
+        }
+
+        self.fragment_shader = fragment_shader.to_string();
+        Ok(())
+    }
+
+    // Builds the pipeline inside a validation scope so an invalid user shader surfaces as
+    // an error instead of panicking through the uncaptured error handler.
+    fn compile(
+        &self,
+        device: &RenderDevice,
+        fragment_shader: &str,
+        format: wgpu::TextureFormat,
+    ) -> Result<FullscreenPipeline, ResourceError> {
+        device
+            .validation_scope(|device| self.build_pipeline(device, fragment_shader, format))
+            .map_err(|err| ResourceError::ShaderLoadFailed(format!("{} {}", self.name, err)))
+    }
+
+    fn create_shader_module(
+        &self,
+        device: &wgpu::Device,
+        fragment_shader: &str,
+    ) -> wgpu::ShaderModule {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&self.name),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{}\n{}", FULLSCREEN_VERTEX_SHADER, fragment_shader).into(),
+            ),
+        })
+    }
+
+    fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        fragment_shader: &str,
+        format: wgpu::TextureFormat,
+    ) -> FullscreenPipeline {
+        let module = self.create_shader_module(device, fragment_shader);
+
+        let mut layout_entries = Vec::with_capacity(self.inputs.len() * 2);
+        for index in 0..self.inputs.len() {
+            let binding = index as u32 * 2;
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            });
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: binding + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&self.name),
+            entries: &layout_entries,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&self.name),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&self.name),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        FullscreenPipeline {
+            format,
+            pipeline,
+            bind_group_layout,
+            bind_group: None,
+        }
+    }
+}
+
+fn create_input_bind_group(
+    device: &RenderDevice,
+    name: &str,
+    layout: &wgpu::BindGroupLayout,
+    inputs: &[(Arc<TextureView>, Arc<Sampler>)],
+) -> Result<wgpu::BindGroup, ResourceError> {
+    let mut entries = Vec::with_capacity(inputs.len() * 2);
+    for (index, (view, sampler)) in inputs.iter().enumerate() {
+        let binding = index as u32 * 2;
+        entries.push(wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(&view.view),
+        });
+        entries.push(wgpu::BindGroupEntry {
+            binding: binding + 1,
+            resource: wgpu::BindingResource::Sampler(sampler.sampler()),
+        });
+    }
+    device
+        .validation_scope(|device| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(name),
+                layout,
+                entries: &entries,
+            })
+        })
+        .map_err(|err| ResourceError::InvalidCreateInfo(format!("inputs of {} {}", name, err)))
+}
+
+impl RenderPass for FullscreenPass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn record(
+        &mut self,
+        device: &RenderDevice,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &RenderTarget,
+    ) -> Result<(), ResourceError> {
+        let ops = color_operations(&self.name, self.color_ops, target)?;
+        let pipeline = match self.pipeline.take() {
+            Some(cached) if cached.format == target.format => cached,
+            _ => self.compile(device, &self.fragment_shader, target.format)?,
+        };
+        let pipeline = self.pipeline.insert(pipeline);
+        let bind_group = match pipeline.bind_group.take() {
+            Some(bind_group) => bind_group,
+            None => create_input_bind_group(
+                device,
+                &self.name,
+                &pipeline.bind_group_layout,
+                &self.inputs,
+            )?,
+        };
+        let bind_group = pipeline.bind_group.insert(bind_group);
+
+        let mut renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.name),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.view,
+                depth_slice: None,
+                resolve_target: None,
+                ops,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        renderpass.set_pipeline(&pipeline.pipeline);
+        renderpass.set_bind_group(0, &*bind_group, &[]);
+        renderpass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
+
+pub enum DrawCommand {
+    Draw {
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    },
+    DrawIndexed {
+        index_buffer: Arc<Buffer>,
+        index_format: wgpu::IndexFormat,
+        index_count: u32,
+        instances: Range<u32>,
+    },
+}
+
+// Issues recorded draw commands with a user built pipeline. Commands are retained
+// across frames until clear_commands() is called.
+pub struct DrawPass {
+    name: String,
+    pipeline: wgpu::RenderPipeline,
+    bind_groups: Vec<wgpu::BindGroup>,
+    vertex_buffers: Vec<Arc<Buffer>>,
+    commands: Vec<DrawCommand>,
+    color_ops: wgpu::Operations<wgpu::Color>,
+}
+
+impl DrawPass {
+    pub fn new(pipeline: wgpu::RenderPipeline, name: &str) -> DrawPass {
+        DrawPass {
+            name: name.to_string(),
+            pipeline,
+            bind_groups: Vec::new(),
+            vertex_buffers: Vec::new(),
+            commands: Vec::new(),
+            color_ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    pub fn with_color_ops(mut self, color_ops: wgpu::Operations<wgpu::Color>) -> DrawPass {
+        self.color_ops = color_ops;
+        self
+    }
+
+    // Bound to the group index matching the insertion order.
+    pub fn with_bind_group(mut self, bind_group: wgpu::BindGroup) -> DrawPass {
+        self.bind_groups.push(bind_group);
+        self
+    }
+
+    // Bound to the vertex buffer slot matching the insertion order.
+    pub fn with_vertex_buffer(mut self, buffer: Arc<Buffer>) -> Result<DrawPass, ResourceError> {
+        if !buffer.info.usage.contains(wgpu::BufferUsages::VERTEX) {
+            return Err(ResourceError::InvalidCreateInfo(format!(
+                "vertex buffer of {} requires VERTEX usage but has {:?}",
+                self.name, buffer.info.usage
+            )));
+        }
+        self.vertex_buffers.push(buffer);
+        Ok(self)
+    }
+
+    pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.commands.push(DrawCommand::Draw {
+            vertices,
+            instances,
+        });
+    }
+
+    pub fn draw_indexed(
+        &mut self,
+        index_buffer: Arc<Buffer>,
+        index_format: wgpu::IndexFormat,
+        index_count: u32,
+        instances: Range<u32>,
+    ) -> Result<(), ResourceError> {
+        if !index_buffer.info.usage.contains(wgpu::BufferUsages::INDEX) {
+            return Err(ResourceError::InvalidCreateInfo(format!(
+                "index buffer of {} requires INDEX usage but has {:?}",
+                self.name, index_buffer.info.usage
+            )));
+        }
+        let required_size = index_count as u64 * index_format.byte_size() as u64;
+        if required_size > index_buffer.buffer.size() {
+            return Err(ResourceError::InvalidCreateInfo(format!(
+                "{} indices of {:?} exceed index buffer size {}",
+                index_count,
+                index_format,
+                index_buffer.buffer.size()
+            )));
+        }
+        self.commands.push(DrawCommand::DrawIndexed {
+            index_buffer,
+            index_format,
+            index_count,
+            instances,
+        });
+        Ok(())
+    }
+
+    pub fn clear_commands(&mut self) {
+        self.commands.clear();
+    }
+}
+
+impl RenderPass for DrawPass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn record(
+        &mut self,
+        _device: &RenderDevice,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &RenderTarget,
+    ) -> Result<(), ResourceError> {
+        let ops = color_operations(&self.name, self.color_ops, target)?;
+        let mut renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.name),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.view,
+                depth_slice: None,
+                resolve_target: None,
+                ops,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        renderpass.set_pipeline(&self.pipeline);
+        for (index, bind_group) in self.bind_groups.iter().enumerate() {
+            renderpass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        for (slot, buffer) in self.vertex_buffers.iter().enumerate() {
+            renderpass.set_vertex_buffer(slot as u32, buffer.buffer.slice(..));
+        }
+
+        for command in &self.commands {
+            match command {
+                DrawCommand::Draw {
+                    vertices,
+                    instances,
+                } => renderpass.draw(vertices.clone(), instances.clone()),
+                DrawCommand::DrawIndexed {
+                    index_buffer,
+                    index_format,
+                    index_count,
+                    instances,
+                } => {
+                    renderpass.set_index_buffer(index_buffer.buffer.slice(..), *index_format);
+                    renderpass.draw_indexed(0..*index_count, 0, instances.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_device::{test_device, test_texture};
+    use crate::render_resource::{BufferCreateInfo, ResourceFlag, TextureViewCreateInfo};
+    use crate::texture::Texture;
+
+    fn target_texture(device: &RenderDevice, name: &str) -> Arc<Texture> {
+        test_texture(
+            device,
+            wgpu::TextureFormat::Rgba8Unorm,
+            1,
+            1,
+            ResourceFlag::NONE,
+            name,
+        )
+    }
+
+    fn full_view(texture: &Arc<Texture>) -> Arc<TextureView> {
+        texture.create_view(TextureViewCreateInfo {
+            base_mip: 0,
+            num_mips: 1,
+            base_slice: 0,
+            num_slices: 1,
+            dimension: None,
+        })
+    }
+
+    fn record_pass(
+        device: &RenderDevice,
+        pass: &mut dyn RenderPass,
+        view: &TextureView,
+        contents_defined: bool,
+    ) -> Result<(), ResourceError> {
+        let mut encoder = device
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let target = RenderTarget {
+            view: &view.view,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            contents_defined,
+        };
+        pass.record(device, &mut encoder, &target)?;
+        device.submit([encoder.finish()]);
+        Ok(())
+    }
+
+    #[test]
+    fn fullscreen_pass_writes_constant_color() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = target_texture(&device, "constant_color");
+        let view = full_view(&texture);
+        let mut pass = FullscreenPass::new(
+            r#"
+@fragment
+fn fs_main(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 1.0, 1.0);
+}
+"#,
+            "constant_color",
+        );
+        record_pass(&device, &mut pass, &view, false).unwrap();
+
+        let pixels = texture.read_pixels(&device, false).unwrap();
+        assert_eq!(pixels.len(), 4 * 4 * 4);
+        for pixel in pixels.chunks_exact(4) {
+            assert_eq!(pixel, [255, 0, 255, 255]);
+        }
+    }
+
+    #[test]
+    fn failed_reload_keeps_previous_pipeline() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = target_texture(&device, "reload");
+        let view = full_view(&texture);
+        let mut pass = FullscreenPass::new(
+            r#"
+@fragment
+fn fs_main(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+}
+"#,
+            "reload",
+        );
+        record_pass(&device, &mut pass, &view, false).unwrap();
+
+        // The module itself is valid, only pipeline creation can notice the missing fs_main.
+        let missing_entry_point = r#"
+@fragment
+fn other_main(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+"#;
+        assert!(matches!(
+            pass.reload(&device, missing_entry_point),
+            Err(ResourceError::ShaderLoadFailed(_))
+        ));
+        assert!(pass.reload(&device, "fn fs_main( {").is_err());
+
+        record_pass(&device, &mut pass, &view, false).unwrap();
+        let pixels = texture.read_pixels(&device, false).unwrap();
+        assert_eq!(&pixels[..4], [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn fullscreen_pass_rejects_invalid_shader() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = target_texture(&device, "invalid_shader");
+        let view = full_view(&texture);
+        let mut pass = FullscreenPass::new("fn fs_main( {", "invalid_shader");
+        assert!(matches!(
+            record_pass(&device, &mut pass, &view, false),
+            Err(ResourceError::ShaderLoadFailed(_))
+        ));
+    }
+
+    // Covers the whole target with one triangle whose corners are picked by index.
+    fn triangle_pipeline(device: &RenderDevice) -> wgpu::RenderPipeline {
+        let device = device.device();
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("indexed_triangle"),
+            source: wgpu::ShaderSource::Wgsl(
+                r#"
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 1.0, 1.0);
+}
+"#
+                .into(),
+            ),
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("indexed_triangle"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    #[test]
+    fn draw_pass_renders_indexed_triangle() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = target_texture(&device, "indexed_draw");
+        let view = full_view(&texture);
+
+        let indices: [u16; 4] = [0, 1, 2, 0];
+        let index_buffer = Buffer::new(
+            &device,
+            BufferCreateInfo {
+                size: std::mem::size_of_val(&indices) as u64,
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            },
+            "indices",
+        );
+        let bytes: Vec<u8> = indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect();
+        device
+            .command_queue()
+            .write_buffer(&index_buffer.buffer, 0, &bytes);
+
+        let mut pass = DrawPass::new(triangle_pipeline(&device), "indexed_draw");
+        pass.draw_indexed(index_buffer, wgpu::IndexFormat::Uint16, 3, 0..1)
+            .unwrap();
+        record_pass(&device, &mut pass, &view, false).unwrap();
+
+        // Cleared to black, so a blue pixel can only come from the indexed draw.
+        let pixels = texture.read_pixels(&device, false).unwrap();
+        for pixel in pixels.chunks_exact(4) {
+            assert_eq!(pixel, [0, 0, 255, 255]);
+        }
+    }
+
+    #[test]
+    fn draw_indexed_requires_index_usage() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let buffer = Buffer::new(
+            &device,
+            BufferCreateInfo {
+                size: 16,
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+            "not_an_index_buffer",
+        );
+        let mut pass = DrawPass::new(triangle_pipeline(&device), "index_usage");
+        assert!(matches!(
+            pass.draw_indexed(buffer, wgpu::IndexFormat::Uint16, 3, 0..1),
+            Err(ResourceError::InvalidCreateInfo(_))
+        ));
+    }
+
+    #[test]
+    fn load_preserves_previous_contents() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = target_texture(&device, "load_target");
+        let view = full_view(&texture);
+        record_pass(&device, &mut ClearPass::new(wgpu::Color::RED), &view, false).unwrap();
+
+        let mut load_pass = ClearPass::new(wgpu::Color::BLUE).with_color_ops(wgpu::Operations {
+            load: wgpu::LoadOp::Load,
+            store: wgpu::StoreOp::Store,
+        });
+        record_pass(&device, &mut load_pass, &view, true).unwrap();
+
+        let pixels = texture.read_pixels(&device, false).unwrap();
+        for pixel in pixels.chunks_exact(4) {
+            assert_eq!(pixel, [255, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn load_on_undefined_target_is_rejected() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = target_texture(&device, "undefined_target");
+        let view = full_view(&texture);
+        let mut load_pass = ClearPass::new(wgpu::Color::BLUE).with_color_ops(wgpu::Operations {
+            load: wgpu::LoadOp::Load,
+            store: wgpu::StoreOp::Store,
+        });
+        assert!(matches!(
+            record_pass(&device, &mut load_pass, &view, false),
+            Err(ResourceError::InvalidCreateInfo(_))
+        ));
+    }
+}
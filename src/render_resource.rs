@@ -1,3 +1,6 @@
+use std::any::Any;
+use std::sync::{Arc, Weak};
+
 use crate::error::ResourceError;
 
 bitflags::bitflags! {
@@ -18,35 +21,141 @@ pub struct ResourceInfo {
 
 pub struct TextureInfo {
     pub base_info: ResourceInfo,
+    pub name: String,
     pub extent: wgpu::Extent3d,
     pub format: wgpu::TextureFormat,
+    pub num_slices: u32,
 }
 
 pub struct TextureCreateInfo {
     pub extent: wgpu::Extent3d,
     pub format: wgpu::TextureFormat,
     pub num_mips: u32,
+    // Number of array layers, 1 for a plain 2D texture.
+    pub num_slices: u32,
+    // ALLOW_UAV requests storage binding, dropped when the format can't be written as storage.
+    pub flags: ResourceFlag,
 }
 
 impl TextureCreateInfo {
+    // 2D array layers are stored in depth_or_array_layers of the texture size.
+    pub fn size(&self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            depth_or_array_layers: self.num_slices.max(1),
+            ..self.extent
+        }
+    }
+
+    // A mip chain longer than the extent allows is rejected by wgpu, it stops at 1x1 instead.
+    pub fn mip_level_count(&self) -> u32 {
+        let max_mips = self.size().max_mips(wgpu::TextureDimension::D2);
+        self.num_mips.clamp(1, max_mips)
+    }
+
+    // Estimated from the format footprint of every mip of every slice, backends add their
+    // own alignment and padding on top.
     pub fn request_size(&self) -> u64 {
-        self.format.theoretical_memory_footprint(self.extent)
+        (0..self.mip_level_count())
+            .map(|mip| {
+                let mip_size = self.size().mip_level_size(mip, wgpu::TextureDimension::D2);
+                self.format.theoretical_memory_footprint(mip_size)
+            })
+            .sum()
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct TextureViewCreateInfo {
     pub base_mip: u32,
     pub num_mips: u32,
     pub base_slice: u32,
     pub num_slices: u32,
+    // None lets wgpu infer the dimension from the texture.
+    pub dimension: Option<wgpu::TextureViewDimension>,
+}
+
+pub struct BufferInfo {
+    pub base_info: ResourceInfo,
+    pub name: String,
+    pub usage: wgpu::BufferUsages,
+}
+
+pub struct BufferCreateInfo {
+    pub size: u64,
+    pub usage: wgpu::BufferUsages,
+}
+
+impl BufferCreateInfo {
+    pub fn request_size(&self) -> u64 {
+        self.size
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerCreateInfo {
+    pub address_mode: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // Values above 1 require linear min/mag/mipmap filters.
+    pub anisotropy_clamp: u16,
+}
+
+impl SamplerCreateInfo {
+    pub fn is_linear(&self) -> bool {
+        self.mag_filter == wgpu::FilterMode::Linear
+            && self.min_filter == wgpu::FilterMode::Linear
+            && self.mipmap_filter == wgpu::FilterMode::Linear
+    }
+}
+
+impl Default for SamplerCreateInfo {
+    fn default() -> Self {
+        SamplerCreateInfo {
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 1,
+        }
+    }
 }
 
-pub trait RenderResource {
+// Send + Sync lets resources be shared across threads and handed out as
+// Arc<dyn Any + Send + Sync> for downcasting.
+pub trait RenderResource: Any + Send + Sync {
+    fn name(&self) -> &str;
     fn resource_flag(&self) -> ResourceFlag;
     fn request_size(&self) -> u64;
     fn allocation_size(&self) -> u64;
+    // Weak references to this resource that only its own caches hold, such as cached views
+    // nobody else uses. Any Weak beyond these means the resource is still in use.
+    fn cached_weak_count(&self) -> usize {
+        0
+    }
 }
 
 pub trait RenderResourceView {
-    fn resource(&self) -> Result<std::sync::Weak<dyn RenderResource>, ResourceError>;
+    fn resource(&self) -> Result<Weak<dyn RenderResource>, ResourceError>;
+}
+
+// Recovers the concrete resource type behind a type-erased resource handle.
+// Returns None when the resource is already dropped or is not a T.
+pub fn downcast_resource<T: RenderResource>(resource: &Weak<dyn RenderResource>) -> Option<Arc<T>> {
+    let resource: Arc<dyn Any + Send + Sync> = resource.upgrade()?;
+    resource.downcast::<T>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_device::test_texture_create_info;
+
+    #[test]
+    fn texture_request_size_covers_mips_and_slices() {
+        let create_info =
+            test_texture_create_info(wgpu::TextureFormat::Rgba8Unorm, 3, 2, ResourceFlag::NONE);
+        // 4x4 + 2x2 + 1x1 texels of 4 bytes for each of the two slices.
+        assert_eq!(create_info.request_size(), (64 + 16 + 4) * 2);
+    }
 }
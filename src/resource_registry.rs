@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use crate::render_device::MemoryReport;
+use crate::render_resource::RenderResource;
+
+pub type MemoryPressureCallback = Box<dyn FnMut(&[Arc<dyn RenderResource>])>;
+
+// Keeps resources alive as a cache and reports which of them can be released once the
+// tracked allocation size goes over budget.
+#[derive(Default)]
+pub struct ResourceRegistry {
+    // Strong references so cached resources survive while nobody uses them, which is
+    // exactly what makes them evictable. Weak ones would drop them right away.
+    resources: Vec<Arc<dyn RenderResource>>,
+    memory_budget: Option<u64>,
+    on_memory_pressure: Option<MemoryPressureCallback>,
+}
+
+impl ResourceRegistry {
+    pub fn new() -> ResourceRegistry {
+        ResourceRegistry::default()
+    }
+
+    // None disables the memory pressure check.
+    pub fn set_memory_budget(&mut self, memory_budget: Option<u64>) {
+        self.memory_budget = memory_budget;
+    }
+
+    // Called with the evictable resources whenever a registration exceeds the budget.
+    // Call release_evictable() afterwards to actually drop them.
+    pub fn set_on_memory_pressure(
+        &mut self,
+        callback: impl FnMut(&[Arc<dyn RenderResource>]) + 'static,
+    ) {
+        self.on_memory_pressure = Some(Box::new(callback));
+    }
+
+    pub fn register(&mut self, resource: Arc<dyn RenderResource>) {
+        self.resources.push(resource);
+        self.check_memory_pressure();
+    }
+
+    pub fn total_allocation_size(&self) -> u64 {
+        self.resources
+            .iter()
+            .map(|resource| resource.allocation_size())
+            .sum()
+    }
+
+    // Backend allocated bytes not accounted for by registered resources. Positive values
+    // point at unregistered resources or allocator overhead, negative ones at resources
+    // whose allocation_size is overestimated.
+    pub fn untracked_allocation_size(&self, report: &MemoryReport) -> i64 {
+        report.allocated_bytes as i64 - self.total_allocation_size() as i64
+    }
+
+    // Resources nobody else holds. Views only keep a Weak to their parent, so a texture
+    // with views in use outside its own view cache is not evictable either.
+    pub fn evictable_resources(&self) -> Vec<Arc<dyn RenderResource>> {
+        self.resources
+            .iter()
+            .filter(|resource| is_evictable(resource))
+            .cloned()
+            .collect()
+    }
+
+    // Returns true when the budget is exceeded, after notifying the pressure callback.
+    pub fn check_memory_pressure(&mut self) -> bool {
+        let Some(memory_budget) = self.memory_budget else {
+            return false;
+        };
+        let total_allocation_size = self.total_allocation_size();
+        if total_allocation_size <= memory_budget {
+            return false;
+        }
+
+        log::warn!(
+            "Tracked allocation size {} exceeds memory budget {}",
+            total_allocation_size,
+            memory_budget
+        );
+        let evictable_resources = self.evictable_resources();
+        if let Some(callback) = self.on_memory_pressure.as_mut() {
+            callback(&evictable_resources);
+        }
+        true
+    }
+
+    // Drops every evictable resource and returns the allocation size released.
+    pub fn release_evictable(&mut self) -> u64 {
+        let mut released_size = 0;
+        self.resources.retain(|resource| {
+            let evictable = is_evictable(resource);
+            if evictable {
+                log::debug!("Release {} under memory pressure", resource.name());
+                released_size += resource.allocation_size();
+            }
+            !evictable
+        });
+        released_size
+    }
+}
+
+fn is_evictable(resource: &Arc<dyn RenderResource>) -> bool {
+    Arc::strong_count(resource) == 1 && Arc::weak_count(resource) <= resource.cached_weak_count()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::render_device::{test_device, test_texture};
+    use crate::render_resource::{ResourceFlag, TextureViewCreateInfo};
+
+    struct FakeResource {
+        name: String,
+        allocation_size: u64,
+    }
+
+    fn fake_resource(name: &str, allocation_size: u64) -> Arc<dyn RenderResource> {
+        Arc::new(FakeResource {
+            name: name.to_string(),
+            allocation_size,
+        })
+    }
+
+    impl RenderResource for FakeResource {
+        fn name(&self) -> &str {
+            &self.name
+        }
+        fn resource_flag(&self) -> ResourceFlag {
+            ResourceFlag::NONE
+        }
+        fn request_size(&self) -> u64 {
+            self.allocation_size
+        }
+        fn allocation_size(&self) -> u64 {
+            self.allocation_size
+        }
+    }
+
+    #[test]
+    fn untracked_allocation_size_compares_report_with_registry() {
+        let mut registry = ResourceRegistry::new();
+        registry.register(fake_resource("a", 100));
+        registry.register(fake_resource("b", 50));
+
+        let report = MemoryReport {
+            allocated_bytes: 200,
+            reserved_bytes: 256,
+            ..Default::default()
+        };
+        assert_eq!(registry.untracked_allocation_size(&report), 50);
+    }
+
+    #[test]
+    fn memory_pressure_reports_unused_resources() {
+        let evicted = Rc::new(RefCell::new(Vec::new()));
+        let callback_evicted = evicted.clone();
+
+        let mut registry = ResourceRegistry::new();
+        registry.set_memory_budget(Some(120));
+        registry.set_on_memory_pressure(move |resources| {
+            *callback_evicted.borrow_mut() = resources
+                .iter()
+                .map(|resource| resource.name().to_string())
+                .collect::<Vec<_>>();
+        });
+
+        let in_use = fake_resource("in_use", 50);
+        registry.register(in_use.clone());
+        registry.register(fake_resource("unused", 50));
+        assert!(evicted.borrow().is_empty());
+
+        registry.register(fake_resource("over_budget", 50));
+        assert_eq!(*evicted.borrow(), ["unused", "over_budget"]);
+
+        assert_eq!(registry.release_evictable(), 100);
+        assert_eq!(registry.total_allocation_size(), 50);
+    }
+
+    #[test]
+    fn texture_with_external_view_is_not_evictable() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = test_texture(
+            &device,
+            wgpu::TextureFormat::Rgba8Unorm,
+            1,
+            1,
+            ResourceFlag::NONE,
+            "viewed",
+        );
+        let view = texture.create_view(TextureViewCreateInfo {
+            base_mip: 0,
+            num_mips: 1,
+            base_slice: 0,
+            num_slices: 1,
+            dimension: None,
+        });
+
+        let mut registry = ResourceRegistry::new();
+        registry.register(texture);
+        assert!(registry.evictable_resources().is_empty());
+
+        // Only the view cache of the texture is left holding the view.
+        drop(view);
+        assert_eq!(registry.evictable_resources().len(), 1);
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use crate::error::ResourceError;
+use crate::render_device::RenderDevice;
+use crate::render_resource::SamplerCreateInfo;
+
+// wgpu clamps anisotropy to 16 on every backend.
+pub const MAX_ANISOTROPY_CLAMP: u16 = 16;
+
+// Without ANISOTROPIC_FILTERING (e.g. GL drivers below 16x) wgpu silently samples with 1.
+pub fn max_anisotropy_clamp(device: &RenderDevice) -> u16 {
+    let flags = device.adapter().get_downlevel_capabilities().flags;
+    if flags.contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING) {
+        MAX_ANISOTROPY_CLAMP
+    } else {
+        1
+    }
+}
+
+pub struct Sampler {
+    pub info: SamplerCreateInfo,
+    pub sampler: wgpu::Sampler,
+}
+
+impl Sampler {
+    pub fn new(
+        device: &RenderDevice,
+        create_info: SamplerCreateInfo,
+        name: &str,
+    ) -> Result<Arc<Sampler>, ResourceError> {
+        let create_info = validate_create_info(create_info, max_anisotropy_clamp(device), name)?;
+
+        let sampler = device.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(name),
+            address_mode_u: create_info.address_mode,
+            address_mode_v: create_info.address_mode,
+            address_mode_w: create_info.address_mode,
+            mag_filter: create_info.mag_filter,
+            min_filter: create_info.min_filter,
+            mipmap_filter: create_info.mipmap_filter,
+            anisotropy_clamp: create_info.anisotropy_clamp,
+            ..Default::default()
+        });
+
+        Ok(Arc::new(Sampler {
+            info: create_info,
+            sampler,
+        }))
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+}
+
+// Checked before any device call, anisotropy above the supported maximum is clamped.
+fn validate_create_info(
+    mut create_info: SamplerCreateInfo,
+    max_anisotropy_clamp: u16,
+    name: &str,
+) -> Result<SamplerCreateInfo, ResourceError> {
+    if create_info.anisotropy_clamp == 0 {
+        return Err(ResourceError::InvalidCreateInfo(format!(
+            "sampler {} requires anisotropy_clamp of at least 1",
+            name
+        )));
+    }
+    // wgpu rejects anisotropic samplers unless every filter is linear.
+    if create_info.anisotropy_clamp > 1 && !create_info.is_linear() {
+        return Err(ResourceError::InvalidCreateInfo(format!(
+            "sampler {} uses anisotropy_clamp {} with non-linear filtering",
+            name, create_info.anisotropy_clamp
+        )));
+    }
+    if create_info.anisotropy_clamp > max_anisotropy_clamp {
+        log::warn!(
+            "Sampler {} anisotropy_clamp {} exceeds supported maximum, clamped to {}",
+            name,
+            create_info.anisotropy_clamp,
+            max_anisotropy_clamp
+        );
+        create_info.anisotropy_clamp = max_anisotropy_clamp;
+    }
+    Ok(create_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_device::test_device;
+
+    #[test]
+    fn anisotropic_sampler_is_created() {
+        let create_info = SamplerCreateInfo {
+            anisotropy_clamp: 8,
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_create_info(create_info, MAX_ANISOTROPY_CLAMP, "anisotropic")
+                .unwrap()
+                .anisotropy_clamp,
+            8
+        );
+
+        let Some(device) = test_device() else {
+            return;
+        };
+        let sampler = Sampler::new(&device, create_info, "anisotropic").unwrap();
+        assert_eq!(
+            sampler.info.anisotropy_clamp,
+            8.min(max_anisotropy_clamp(&device))
+        );
+    }
+
+    #[test]
+    fn anisotropy_above_maximum_is_clamped() {
+        let create_info = SamplerCreateInfo {
+            anisotropy_clamp: 64,
+            ..Default::default()
+        };
+        let clamped = validate_create_info(create_info, MAX_ANISOTROPY_CLAMP, "clamped").unwrap();
+        assert_eq!(clamped.anisotropy_clamp, MAX_ANISOTROPY_CLAMP);
+
+        // Devices without anisotropic filtering only sample with 1.
+        let clamped = validate_create_info(create_info, 1, "unsupported").unwrap();
+        assert_eq!(clamped.anisotropy_clamp, 1);
+    }
+
+    #[test]
+    fn anisotropy_with_nearest_filter_is_rejected() {
+        let create_info = SamplerCreateInfo {
+            min_filter: wgpu::FilterMode::Nearest,
+            anisotropy_clamp: 4,
+            ..Default::default()
+        };
+        assert!(matches!(
+            validate_create_info(create_info, MAX_ANISOTROPY_CLAMP, "nearest"),
+            Err(ResourceError::InvalidCreateInfo(_))
+        ));
+    }
+
+    #[test]
+    fn zero_anisotropy_is_rejected() {
+        let create_info = SamplerCreateInfo {
+            anisotropy_clamp: 0,
+            ..Default::default()
+        };
+        assert!(validate_create_info(create_info, MAX_ANISOTROPY_CLAMP, "zero").is_err());
+    }
+}
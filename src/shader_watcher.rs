@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use notify::Watcher;
+
+use crate::error::ResourceError;
+
+// Watches registered WGSL files and hands their new source to the caller on change.
+pub struct ShaderWatcher {
+    watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    paths: HashSet<PathBuf>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Result<ShaderWatcher, ResourceError> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(sender).map_err(|err| {
+            ResourceError::ShaderLoadFailed(format!("Failed to create shader watcher {}", err))
+        })?;
+        Ok(ShaderWatcher {
+            watcher,
+            events,
+            paths: HashSet::new(),
+        })
+    }
+
+    // Returns the canonical path reported back by poll().
+    pub fn watch(&mut self, path: &Path) -> Result<PathBuf, ResourceError> {
+        let path = path.canonicalize().map_err(|err| {
+            ResourceError::ShaderLoadFailed(format!("{} {}", path.display(), err))
+        })?;
+        // Editors often save by replacing the file, which drops a watch on the file itself,
+        // so the parent directory is watched and events are filtered by path.
+        let directory = path.parent().unwrap_or(&path);
+        self.watcher
+            .watch(directory, notify::RecursiveMode::NonRecursive)
+            .map_err(|err| {
+                ResourceError::ShaderLoadFailed(format!("{} {}", path.display(), err))
+            })?;
+        self.paths.insert(path.clone());
+        Ok(path)
+    }
+
+    // Drains pending file events and calls on_reload once per changed shader.
+    pub fn poll(&self, mut on_reload: impl FnMut(&Path, &str)) {
+        let mut changed = HashSet::new();
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if event.kind.is_modify() || event.kind.is_create() => changed.extend(
+                    event
+                        .paths
+                        .into_iter()
+                        .filter(|path| self.paths.contains(path)),
+                ),
+                Ok(_) => (),
+                Err(err) => log::warn!("Shader watcher error {:?}", err),
+            }
+        }
+
+        for path in changed {
+            match std::fs::read_to_string(&path) {
+                Ok(source) => on_reload(&path, &source),
+                Err(err) => log::error!("Failed to read shader {} {}", path.display(), err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn poll_reports_modified_shader() {
+        let directory =
+            std::env::temp_dir().join(format!("p0_shader_watcher_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("watched.wgsl");
+        std::fs::write(&path, "// initial").unwrap();
+
+        let mut watcher = ShaderWatcher::new().unwrap();
+        let watched = watcher.watch(&path).unwrap();
+        std::fs::write(&path, "// modified").unwrap();
+
+        let mut reloaded = None;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while reloaded.is_none() && Instant::now() < deadline {
+            watcher.poll(|path, source| reloaded = Some((path.to_path_buf(), source.to_string())));
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(reloaded, Some((watched, "// modified".to_string())));
+    }
+}
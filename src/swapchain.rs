@@ -2,9 +2,66 @@ use std::sync::{Arc, Weak};
 
 use crate::{error::DeviceError, render_device::RenderDevice};
 
+#[derive(Clone, Debug)]
+pub struct SwapChainConfig {
+    // Add COPY_SRC to read the swapchain image back directly.
+    pub surface_usage: wgpu::TextureUsages,
+    pub present_mode: wgpu::PresentMode,
+    // None picks a latency matching present_mode, see frame_latency_for_present_mode().
+    pub desired_maximum_frame_latency: Option<u32>,
+    // Formats views of the surface texture may use. None requests both the linear and
+    // sRGB variant of the surface format, an empty list disables reinterpretation.
+    pub view_formats: Option<Vec<wgpu::TextureFormat>>,
+}
+
+impl Default for SwapChainConfig {
+    fn default() -> Self {
+        SwapChainConfig {
+            surface_usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: None,
+            view_formats: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PresentStats {
+    pub frames_presented: u64,
+    // Frames dropped because the image could not be acquired (outdated, lost, timeout,
+    // minimized window).
+    pub frames_skipped: u64,
+    // Every surface configuration with a non-zero extent, including the initial one.
+    pub reconfigurations: u64,
+}
+
+impl PresentStats {
+    // Counts the skipped frame and tells the caller how to recover from the failure.
+    pub fn record_acquire_error(
+        &mut self,
+        err: wgpu::SurfaceError,
+    ) -> Result<AcquireRecovery, DeviceError> {
+        self.frames_skipped += 1;
+        acquire_recovery(err)
+    }
+
+    // Returns false for zero sized extents, which must not be configured.
+    pub fn record_configuration(&mut self, extent: winit::dpi::PhysicalSize<u32>) -> bool {
+        if extent.width == 0 || extent.height == 0 {
+            return false;
+        }
+        self.reconfigurations += 1;
+        true
+    }
+}
+
 pub struct SwapChain {
     pub(crate) surface: wgpu::Surface<'static>,
     pub(crate) surface_format: wgpu::TextureFormat,
+    pub(crate) surface_usage: wgpu::TextureUsages,
+    pub(crate) view_formats: Vec<wgpu::TextureFormat>,
+    pub(crate) present_mode: wgpu::PresentMode,
+    pub(crate) frame_latency: u32,
     pub(crate) window: Weak<winit::window::Window>,
     pub(crate) size: winit::dpi::PhysicalSize<u32>,
 }
@@ -13,6 +70,7 @@ impl SwapChain {
     pub fn new(
         device: &RenderDevice,
         window: Arc<winit::window::Window>,
+        config: &SwapChainConfig,
     ) -> Result<SwapChain, DeviceError> {
         let instance = device.instance();
         let adapter = device.adapter();
@@ -24,35 +82,119 @@ impl SwapChain {
         // TODO : decide surface format candidates and if no candidate availabe, terminate the app
         let surface_format = cap.formats[0];
         log::info!("Surface format {:?} selected", surface_format);
+        let surface_usage = select_surface_usage(config.surface_usage, cap.usages);
+        let supports_view_formats = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS);
+        let view_formats = select_view_formats(
+            surface_format,
+            config.view_formats.as_deref(),
+            supports_view_formats,
+        );
+        let frame_latency = config
+            .desired_maximum_frame_latency
+            .unwrap_or_else(|| frame_latency_for_present_mode(config.present_mode));
 
         let size = window.inner_size();
 
-        Ok(SwapChain {
+        let swapchain = SwapChain {
             surface,
             surface_format,
+            surface_usage,
+            view_formats,
+            present_mode: config.present_mode,
+            frame_latency,
             window: Arc::downgrade(&window),
             size,
-        })
+        };
+        log::debug!(
+            "{}",
+            capability_summary(&cap, &swapchain.surface_configuration(size))
+        );
+        Ok(swapchain)
     }
 
-    pub fn configure_surface(
-        &mut self,
-        device: &RenderDevice,
+    // Logged once from new(), call again to inspect the surface after reconfiguration.
+    pub fn log_capabilities(&self, adapter: &wgpu::Adapter) {
+        log::debug!(
+            "{}",
+            capability_summary(
+                &self.surface.get_capabilities(adapter),
+                &self.surface_configuration(self.size)
+            )
+        );
+    }
+
+    pub fn surface_configuration(
+        &self,
         extent: winit::dpi::PhysicalSize<u32>,
-    ) {
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    ) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            usage: self.surface_usage,
             format: self.surface_format,
-            // Request compatibility with the sRGB-format texture view we‘re going to create later.
-            view_formats: vec![self.surface_format.add_srgb_suffix()],
+            // Formats render() may view the surface texture as, see select_view_formats().
+            view_formats: self.view_formats.clone(),
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
             width: extent.width,
             height: extent.height,
-            desired_maximum_frame_latency: 2,
-            present_mode: wgpu::PresentMode::AutoVsync,
-        };
-        self.surface.configure(device.device(), &surface_config);
+            desired_maximum_frame_latency: self.frame_latency,
+            present_mode: self.present_mode,
+        }
+    }
+
+    // Counts into the caller's stats, which outlive the swapchain across suspend/resume.
+    pub fn configure_surface(
+        &mut self,
+        device: &RenderDevice,
+        extent: winit::dpi::PhysicalSize<u32>,
+        stats: &mut PresentStats,
+    ) {
+        // The size is still recorded so acquire() skips frames until the next resize.
         self.size = extent;
+        // wgpu rejects zero sized surfaces, which a minimized window reports.
+        if !stats.record_configuration(extent) {
+            return;
+        }
+        let surface_config = self.surface_configuration(extent);
+        self.surface.configure(device.device(), &surface_config);
+    }
+
+    // Returns None when the frame has to be skipped. Outdated or lost surfaces are
+    // reconfigured so the next acquire can succeed.
+    pub fn acquire(
+        &mut self,
+        device: &RenderDevice,
+        stats: &mut PresentStats,
+    ) -> Result<Option<wgpu::SurfaceTexture>, DeviceError> {
+        // A minimized window has a zero sized surface which can't be acquired.
+        if self.size.width == 0 || self.size.height == 0 {
+            stats.frames_skipped += 1;
+            return Ok(None);
+        }
+        match self.surface.get_current_texture() {
+            Ok(surface_texture) => Ok(Some(surface_texture)),
+            Err(err) => self.handle_acquire_error(device, err, stats).map(|_| None),
+        }
+    }
+
+    pub fn handle_acquire_error(
+        &mut self,
+        device: &RenderDevice,
+        err: wgpu::SurfaceError,
+        stats: &mut PresentStats,
+    ) -> Result<(), DeviceError> {
+        match stats.record_acquire_error(err)? {
+            AcquireRecovery::SkipFrame => (),
+            AcquireRecovery::Reconfigure => {
+                let extent = self
+                    .window
+                    .upgrade()
+                    .map_or(self.size, |window| window.inner_size());
+                self.configure_surface(device, extent, stats);
+            }
+        }
+        Ok(())
     }
 
     pub fn surface(&self) -> &wgpu::Surface<'_> {
@@ -63,6 +205,32 @@ impl SwapChain {
         &self.surface_format
     }
 
+    pub fn view_formats(&self) -> &[wgpu::TextureFormat] {
+        &self.view_formats
+    }
+
+    // Format for render target views of the surface, sRGB whenever reinterpretation allows it.
+    pub fn view_format(&self) -> wgpu::TextureFormat {
+        let srgb_format = self.surface_format.add_srgb_suffix();
+        if srgb_format == self.surface_format || self.view_formats.contains(&srgb_format) {
+            srgb_format
+        } else {
+            self.surface_format
+        }
+    }
+
+    pub fn surface_usage(&self) -> wgpu::TextureUsages {
+        self.surface_usage
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.present_mode
+    }
+
+    pub fn frame_latency(&self) -> u32 {
+        self.frame_latency
+    }
+
     pub fn need_configuration(&self) -> bool {
         if let Some(window) = self.window.upgrade() {
             self.size != window.inner_size()
@@ -79,3 +247,273 @@ impl SwapChain {
         }
     }
 }
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AcquireRecovery {
+    SkipFrame,
+    Reconfigure,
+}
+
+// Decides how to recover from a failed image acquisition, the frame is skipped either way.
+pub fn acquire_recovery(err: wgpu::SurfaceError) -> Result<AcquireRecovery, DeviceError> {
+    match err {
+        wgpu::SurfaceError::Timeout => {
+            log::warn!("Swapchain image acquisition timed out, frame skipped");
+            Ok(AcquireRecovery::SkipFrame)
+        }
+        wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost => {
+            log::info!("Swapchain is {:?}, reconfigure surface", err);
+            Ok(AcquireRecovery::Reconfigure)
+        }
+        wgpu::SurfaceError::OutOfMemory => Err(DeviceError::OutOfMemory),
+        err => {
+            log::error!("Failed to acquire swapchain image {:?}, frame skipped", err);
+            Ok(AcquireRecovery::SkipFrame)
+        }
+    }
+}
+
+// Everything the surface supports followed by what was picked from it.
+pub fn capability_summary(
+    cap: &wgpu::SurfaceCapabilities,
+    config: &wgpu::SurfaceConfiguration,
+) -> String {
+    format!(
+        "Surface capabilities : formats {:?}, present modes {:?}, alpha modes {:?}, usages {:?}\n\
+         Selected surface configuration : format {:?}, present mode {:?}, alpha mode {:?}, usage {:?}, frame latency {}",
+        cap.formats,
+        cap.present_modes,
+        cap.alpha_modes,
+        cap.usages,
+        config.format,
+        config.present_mode,
+        config.alpha_mode,
+        config.usage,
+        config.desired_maximum_frame_latency,
+    )
+}
+
+// Keeps the supported subset of the requested usage. Surfaces always support
+// RENDER_ATTACHMENT, so it is added back instead of failing surface configuration.
+pub fn select_surface_usage(
+    requested: wgpu::TextureUsages,
+    supported: wgpu::TextureUsages,
+) -> wgpu::TextureUsages {
+    let dropped = requested - supported;
+    if !dropped.is_empty() {
+        log::warn!(
+            "Surface usage {:?} is not supported (supported {:?}), dropping it",
+            dropped,
+            supported
+        );
+    }
+    (requested & supported) | wgpu::TextureUsages::RENDER_ATTACHMENT
+}
+
+// Low latency modes replace queued frames anyway, so a single frame in flight keeps
+// input latency down. Vsync'd modes queue presents and need one more to avoid stalls.
+pub fn frame_latency_for_present_mode(present_mode: wgpu::PresentMode) -> u32 {
+    match present_mode {
+        wgpu::PresentMode::Immediate
+        | wgpu::PresentMode::Mailbox
+        | wgpu::PresentMode::AutoNoVsync => 1,
+        wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed | wgpu::PresentMode::AutoVsync => {
+            2
+        }
+    }
+}
+
+// A surface texture can only be viewed as itself or its sRGB/linear counterpart.
+pub fn is_view_format_compatible(
+    surface_format: wgpu::TextureFormat,
+    view_format: wgpu::TextureFormat,
+) -> bool {
+    surface_format.remove_srgb_suffix() == view_format.remove_srgb_suffix()
+}
+
+// Without a request the surface can be viewed as both its linear and sRGB variant.
+// Requested formats that are not a reinterpretation of the surface format are dropped.
+// Backends lacking DownlevelFlags::SURFACE_VIEW_FORMATS (GL, WebGL2) only allow the
+// surface format itself, so nothing else is kept there.
+pub fn select_view_formats(
+    surface_format: wgpu::TextureFormat,
+    requested: Option<&[wgpu::TextureFormat]>,
+    supports_view_formats: bool,
+) -> Vec<wgpu::TextureFormat> {
+    if !supports_view_formats {
+        if let Some(requested) = requested {
+            if requested.iter().any(|format| *format != surface_format) {
+                log::warn!(
+                    "Surface view formats {:?} are not supported by the adapter, ignored",
+                    requested
+                );
+            }
+        }
+        return Vec::new();
+    }
+    let Some(requested) = requested else {
+        let mut view_formats = vec![surface_format.remove_srgb_suffix()];
+        if surface_format.add_srgb_suffix() != surface_format.remove_srgb_suffix() {
+            view_formats.push(surface_format.add_srgb_suffix());
+        }
+        return view_formats;
+    };
+
+    let mut view_formats = Vec::with_capacity(requested.len());
+    for view_format in requested {
+        if !is_view_format_compatible(surface_format, *view_format) {
+            log::warn!(
+                "View format {:?} is not a reinterpretation of surface format {:?}, ignored",
+                view_format,
+                surface_format
+            );
+        } else if !view_formats.contains(view_format) {
+            view_formats.push(*view_format);
+        }
+    }
+    view_formats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_usage_keeps_supported_bits() {
+        let supported = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+        assert_eq!(
+            select_surface_usage(
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::STORAGE_BINDING,
+                supported
+            ),
+            supported
+        );
+        assert_eq!(
+            select_surface_usage(wgpu::TextureUsages::COPY_SRC, supported),
+            supported
+        );
+        assert_eq!(
+            select_surface_usage(
+                wgpu::TextureUsages::STORAGE_BINDING,
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            ),
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        );
+    }
+
+    #[test]
+    fn frame_latency_follows_present_mode() {
+        for (present_mode, latency) in [
+            (wgpu::PresentMode::Immediate, 1),
+            (wgpu::PresentMode::Mailbox, 1),
+            (wgpu::PresentMode::AutoNoVsync, 1),
+            (wgpu::PresentMode::Fifo, 2),
+            (wgpu::PresentMode::FifoRelaxed, 2),
+            (wgpu::PresentMode::AutoVsync, 2),
+        ] {
+            assert_eq!(
+                frame_latency_for_present_mode(present_mode),
+                latency,
+                "{:?}",
+                present_mode
+            );
+        }
+    }
+
+    #[test]
+    fn capability_summary_contains_selected_configuration() {
+        let cap = wgpu::SurfaceCapabilities {
+            formats: vec![
+                wgpu::TextureFormat::Bgra8UnormSrgb,
+                wgpu::TextureFormat::Rgba16Float,
+            ],
+            present_modes: vec![wgpu::PresentMode::Fifo, wgpu::PresentMode::Mailbox],
+            alpha_modes: vec![wgpu::CompositeAlphaMode::Opaque],
+            usages: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba16Float,
+            width: 64,
+            height: 64,
+            present_mode: wgpu::PresentMode::Mailbox,
+            desired_maximum_frame_latency: 1,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: Vec::new(),
+        };
+        let summary = capability_summary(&cap, &config);
+        let selected = summary
+            .split("Selected surface configuration")
+            .nth(1)
+            .unwrap();
+        assert!(selected.contains("format Rgba16Float"));
+        assert!(selected.contains("present mode Mailbox"));
+    }
+
+    #[test]
+    fn view_formats_are_reinterpretations_of_surface_format() {
+        let surface_format = wgpu::TextureFormat::Bgra8Unorm;
+        assert_eq!(
+            select_view_formats(surface_format, None, true),
+            vec![
+                wgpu::TextureFormat::Bgra8Unorm,
+                wgpu::TextureFormat::Bgra8UnormSrgb
+            ]
+        );
+        assert_eq!(
+            select_view_formats(wgpu::TextureFormat::Rgba16Float, None, true),
+            vec![wgpu::TextureFormat::Rgba16Float]
+        );
+
+        let requested = [
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+        ];
+        assert_eq!(
+            select_view_formats(surface_format, Some(&requested), true),
+            vec![wgpu::TextureFormat::Bgra8UnormSrgb]
+        );
+        assert!(select_view_formats(surface_format, Some(&[]), true).is_empty());
+
+        // GL and WebGL2 reject any view format, so neither the default nor a request is kept.
+        assert!(select_view_formats(surface_format, None, false).is_empty());
+        assert!(select_view_formats(surface_format, Some(&requested), false).is_empty());
+    }
+
+    #[test]
+    fn outdated_surfaces_are_reconfigured() {
+        let extent = winit::dpi::PhysicalSize::new(64, 64);
+        let mut stats = PresentStats::default();
+        // Same bookkeeping as SwapChain::handle_acquire_error() without a surface.
+        for _ in 0..3 {
+            let recovery = stats
+                .record_acquire_error(wgpu::SurfaceError::Outdated)
+                .unwrap();
+            assert_eq!(recovery, AcquireRecovery::Reconfigure);
+            assert!(stats.record_configuration(extent));
+        }
+        assert_eq!(stats.frames_skipped, 3);
+        assert_eq!(stats.reconfigurations, 3);
+        assert_eq!(stats.frames_presented, 0);
+
+        // A minimized window skips the frame without configuring the surface.
+        assert!(!stats.record_configuration(winit::dpi::PhysicalSize::new(0, 64)));
+        assert_eq!(stats.reconfigurations, 3);
+
+        assert_eq!(
+            acquire_recovery(wgpu::SurfaceError::Lost).unwrap(),
+            AcquireRecovery::Reconfigure
+        );
+        assert_eq!(
+            acquire_recovery(wgpu::SurfaceError::Timeout).unwrap(),
+            AcquireRecovery::SkipFrame
+        );
+        assert!(matches!(
+            acquire_recovery(wgpu::SurfaceError::OutOfMemory),
+            Err(DeviceError::OutOfMemory)
+        ));
+    }
+}
@@ -1,16 +1,24 @@
-use std::sync::{Arc, Weak};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
 use wgpu::TextureViewDescriptor;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::buffer::Buffer;
 use crate::error::ResourceError;
+use crate::mipmap::is_storage_format_supported;
 use crate::render_device::RenderDevice;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render_resource::BufferCreateInfo;
 use crate::render_resource::{
-    RenderResource, RenderResourceView, ResourceFlag, TextureCreateInfo, TextureInfo,
-    TextureViewCreateInfo,
+    downcast_resource, RenderResource, RenderResourceView, ResourceFlag, TextureCreateInfo,
+    TextureInfo, TextureViewCreateInfo,
 };
 
 pub struct Texture {
     pub info: TextureInfo,
     pub texture: wgpu::Texture,
+    // Views are cached per create info as the same views are requested every frame.
+    view_cache: Mutex<HashMap<TextureViewCreateInfo, Arc<TextureView>>>,
 }
 
 pub struct TextureView {
@@ -21,43 +29,98 @@ pub struct TextureView {
 impl Texture {
     // Texture::new() returns Arc<Texture>
     pub fn new(device: &RenderDevice, create_info: TextureCreateInfo, name: &str) -> Arc<Texture> {
-        let device: &wgpu::Device = device.device();
+        let mut flags = create_info.flags;
+        let requested_usage = wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::RENDER_ATTACHMENT;
+        // Depth formats can't be copied and compressed formats can't be rendered to, so
+        // only the usages the format supports on this adapter are kept.
+        let allowed_usage = device
+            .adapter()
+            .get_texture_format_features(create_info.format)
+            .allowed_usages;
+        let mut usage = requested_usage & allowed_usage;
+        if usage != requested_usage {
+            log::debug!(
+                "{} format {:?} doesn't support {:?}",
+                name,
+                create_info.format,
+                requested_usage - usage
+            );
+        }
+        // Storage writes are opt-in, compute passes such as mipmap generation check
+        // ALLOW_UAV before binding the texture as storage.
+        if flags.contains(ResourceFlag::ALLOW_UAV) {
+            if is_storage_format_supported(device, create_info.format) {
+                usage |= wgpu::TextureUsages::STORAGE_BINDING;
+            } else {
+                log::warn!(
+                    "{} format {:?} can't be bound as storage, ALLOW_UAV is dropped",
+                    name,
+                    create_info.format
+                );
+                flags.remove(ResourceFlag::ALLOW_UAV);
+            }
+        }
+
+        let num_mips = create_info.mip_level_count();
+        if num_mips != create_info.num_mips {
+            log::warn!(
+                "{} requested {} mips, using {} for extent {}x{}",
+                name,
+                create_info.num_mips,
+                num_mips,
+                create_info.extent.width,
+                create_info.extent.height
+            );
+        }
 
         let texture_desc = wgpu::TextureDescriptor {
             label: Some(name),
-            size: create_info.extent,
-            mip_level_count: 1,
+            size: create_info.size(),
+            mip_level_count: num_mips,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: create_info.format,
+            usage,
             view_formats: &[],
         };
 
-        let texture = device.create_texture(&texture_desc);
+        let (texture, allocation_size) = device
+            .measure_allocation(create_info.request_size(), || {
+                device.device().create_texture(&texture_desc)
+            });
 
         Arc::new(Texture {
             texture,
+            view_cache: Mutex::new(HashMap::new()),
             info: TextureInfo {
+                name: name.to_string(),
                 base_info: crate::render_resource::ResourceInfo {
-                    flags: crate::render_resource::ResourceFlag::NONE,
+                    flags,
                     request_size: create_info.request_size(),
-                    allocation_size: 0, // TODO : check how to know actual device memory footprint
+                    allocation_size,
                 },
                 extent: create_info.extent,
                 format: create_info.format,
+                num_slices: create_info.num_slices.max(1),
             },
         })
     }
 
-    // TextureView creation now expects Arc<Texture>, returns TextureView with Weak<Texture>
-    pub fn create_view(self: &Arc<Self>, view_info: TextureViewCreateInfo) -> TextureView {
+    // TextureView creation now expects Arc<Texture>, returns TextureView with Weak<Texture>.
+    // Repeated requests with the same view_info return the cached view.
+    pub fn create_view(self: &Arc<Self>, view_info: TextureViewCreateInfo) -> Arc<TextureView> {
+        let mut view_cache = self.view_cache.lock().unwrap();
+        if let Some(view) = view_cache.get(&view_info) {
+            return view.clone();
+        }
+
         let texture_view = self.texture.create_view(&TextureViewDescriptor {
             label: None,
             format: Some(self.info.format),
-            dimension: None,
+            dimension: view_info.dimension,
             usage: None,
             aspect: wgpu::TextureAspect::All,
             base_mip_level: view_info.base_mip,
@@ -65,14 +128,166 @@ impl Texture {
             base_array_layer: view_info.base_slice,
             array_layer_count: Some(view_info.num_slices),
         });
-        TextureView {
+        let view = Arc::new(TextureView {
             parent: Arc::downgrade(self),
             view: texture_view,
+        });
+        view_cache.insert(view_info, view.clone());
+        view
+    }
+
+    // Drops the cached views. Views still held by callers stay valid.
+    pub fn clear_view_cache(&self) {
+        self.view_cache.lock().unwrap().clear();
+    }
+
+    // Copies mip 0 of the first slice into tightly packed rows.
+    // With swizzle_to_rgba, BGRA8 formats are reordered to RGBA8 on the CPU. That touches
+    // every pixel once, about the cost of another copy of the image, so leave it off when
+    // the consumer handles BGRA itself.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_pixels(
+        &self,
+        device: &RenderDevice,
+        swizzle_to_rgba: bool,
+    ) -> Result<Vec<u8>, ResourceError> {
+        self.read_mip_pixels(device, 0, swizzle_to_rgba)
+    }
+
+    // Same as read_pixels() for the given mip of the first slice.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_mip_pixels(
+        &self,
+        device: &RenderDevice,
+        mip: u32,
+        swizzle_to_rgba: bool,
+    ) -> Result<Vec<u8>, ResourceError> {
+        read_texture_pixels(device, &self.texture, mip, &self.info.name, swizzle_to_rgba)
+    }
+
+    // Creates a D2Array view over [base_slice, base_slice + num_slices) of every mip.
+    pub fn create_array_view(
+        self: &Arc<Self>,
+        base_slice: u32,
+        num_slices: u32,
+    ) -> Result<Arc<TextureView>, ResourceError> {
+        let end_slice = base_slice.checked_add(num_slices);
+        if num_slices == 0 || end_slice.is_none_or(|end| end > self.info.num_slices) {
+            return Err(ResourceError::InvalidCreateInfo(format!(
+                "slice range {} + {} is out of texture array bounds 0..{}",
+                base_slice, num_slices, self.info.num_slices
+            )));
         }
+        Ok(self.create_view(TextureViewCreateInfo {
+            base_mip: 0,
+            num_mips: self.texture.mip_level_count(),
+            base_slice,
+            num_slices,
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+        }))
+    }
+}
+
+// Copies one mip of the first slice of any texture with COPY_SRC usage into tightly
+// packed rows. The copy is fenced on its own submission so the data is never read
+// before the GPU wrote it.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read_texture_pixels(
+    device: &RenderDevice,
+    texture: &wgpu::Texture,
+    mip: u32,
+    name: &str,
+    swizzle_to_rgba: bool,
+) -> Result<Vec<u8>, ResourceError> {
+    let format = texture.format();
+    let bytes_per_pixel = format.block_copy_size(None).ok_or_else(|| {
+        ResourceError::InvalidCreateInfo(format!(
+            "format {:?} of {} can't be read back",
+            format, name
+        ))
+    })?;
+    if mip >= texture.mip_level_count() {
+        return Err(ResourceError::InvalidCreateInfo(format!(
+            "mip {} of {} is out of bounds 0..{}",
+            mip,
+            name,
+            texture.mip_level_count()
+        )));
+    }
+    let extent = texture.size().mip_level_size(mip, texture.dimension());
+    let width = extent.width;
+    let height = extent.height;
+    let unpadded_bytes_per_row = width * bytes_per_pixel;
+    let padded_bytes_per_row =
+        unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let readback = Buffer::new(
+        device,
+        BufferCreateInfo {
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        },
+        &format!("{} readback", name),
+    );
+
+    let mut encoder = device.device().create_command_encoder(&Default::default());
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: mip,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &readback.buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    let fence = device.submit([encoder.finish()]);
+
+    let padded = pollster::block_on(readback.map_read_async_after(device, &fence))?;
+    let mut pixels: Vec<u8> = padded
+        .chunks_exact(padded_bytes_per_row as usize)
+        .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+        .copied()
+        .collect();
+    if swizzle_to_rgba && is_bgra8_format(format) {
+        swizzle_bgra8_to_rgba8(&mut pixels);
+    }
+    Ok(pixels)
+}
+
+// Swapchains commonly use BGRA on Windows while most image consumers expect RGBA.
+pub fn is_bgra8_format(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+pub fn swizzle_bgra8_to_rgba8(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
     }
 }
 
+pub fn downcast_texture(resource: &Weak<dyn RenderResource>) -> Option<Arc<Texture>> {
+    downcast_resource::<Texture>(resource)
+}
+
 impl RenderResource for Texture {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
     fn resource_flag(&self) -> ResourceFlag {
         self.info.base_info.flags
     }
@@ -82,6 +297,14 @@ impl RenderResource for Texture {
     fn allocation_size(&self) -> u64 {
         self.info.base_info.allocation_size
     }
+    fn cached_weak_count(&self) -> usize {
+        self.view_cache
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|view| Arc::strong_count(view) == 1)
+            .count()
+    }
 }
 
 impl RenderResourceView for TextureView {
@@ -92,3 +315,205 @@ impl RenderResourceView for TextureView {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_device::{test_device, test_texture};
+
+    fn array_texture(device: &RenderDevice, num_slices: u32, name: &str) -> Arc<Texture> {
+        test_texture(
+            device,
+            wgpu::TextureFormat::Rgba8Unorm,
+            1,
+            num_slices,
+            ResourceFlag::NONE,
+            name,
+        )
+    }
+
+    #[test]
+    fn array_view_covers_requested_slices() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = array_texture(&device, 4, "array");
+        assert_eq!(texture.texture.depth_or_array_layers(), 4);
+
+        let view = texture.create_array_view(1, 2).unwrap();
+        assert!(view.parent.upgrade().is_some());
+        assert!(texture.create_array_view(3, 2).is_err());
+        assert!(texture.create_array_view(0, 0).is_err());
+    }
+
+    #[test]
+    fn array_view_rejects_overflowing_range() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = array_texture(&device, 4, "overflow");
+        assert!(matches!(
+            texture.create_array_view(u32::MAX, 2),
+            Err(ResourceError::InvalidCreateInfo(_))
+        ));
+    }
+
+    #[test]
+    fn mip_count_is_clamped_to_extent() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = test_texture(
+            &device,
+            wgpu::TextureFormat::Rgba8Unorm,
+            16,
+            1,
+            ResourceFlag::NONE,
+            "clamped_mips",
+        );
+        assert_eq!(texture.texture.mip_level_count(), 3);
+    }
+
+    #[test]
+    fn repeated_view_requests_share_cached_view() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = array_texture(&device, 1, "view_cache");
+        let view_info = TextureViewCreateInfo {
+            base_mip: 0,
+            num_mips: 1,
+            base_slice: 0,
+            num_slices: 1,
+            dimension: None,
+        };
+        let first = texture.create_view(view_info);
+        let second = texture.create_view(view_info);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        texture.clear_view_cache();
+        let third = texture.create_view(view_info);
+        assert!(!Arc::ptr_eq(&first, &third));
+    }
+
+    #[test]
+    fn view_resource_downcasts_to_texture() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = array_texture(&device, 1, "downcast");
+        let view = texture.create_view(TextureViewCreateInfo {
+            base_mip: 0,
+            num_mips: 1,
+            base_slice: 0,
+            num_slices: 1,
+            dimension: None,
+        });
+        let resource = view.resource().unwrap();
+
+        let downcast = downcast_texture(&resource).unwrap();
+        assert!(Arc::ptr_eq(&downcast, &texture));
+        assert!(downcast_resource::<Buffer>(&resource).is_none());
+
+        drop((texture, downcast));
+        assert!(downcast_texture(&resource).is_none());
+        assert!(matches!(view.resource(), Err(ResourceError::Orphan)));
+    }
+
+    #[test]
+    fn name_is_kept_for_debugging() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture: Arc<dyn RenderResource> = array_texture(&device, 1, "albedo");
+        assert_eq!(texture.name(), "albedo");
+
+        let create_info = BufferCreateInfo {
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM,
+        };
+        let buffer: Arc<dyn RenderResource> = Buffer::new(&device, create_info, "camera");
+        assert_eq!(buffer.name(), "camera");
+    }
+
+    #[test]
+    fn allocation_size_follows_allocator_report() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = array_texture(&device, 2, "measured");
+        assert_eq!(texture.request_size(), 4 * 4 * 4 * 2);
+        if device.memory_report().is_some() {
+            // Real allocations are aligned up, never smaller than the texel data.
+            assert!(texture.allocation_size() >= texture.request_size());
+        } else {
+            assert_eq!(texture.allocation_size(), texture.request_size());
+        }
+    }
+
+    #[test]
+    fn storage_usage_is_opt_in() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        let texture = array_texture(&device, 1, "no_storage");
+        assert!(!texture
+            .texture
+            .usage()
+            .contains(wgpu::TextureUsages::STORAGE_BINDING));
+        assert!(texture.info.base_info.flags.is_empty());
+
+        // BGRA can't be written by the downsample shader, so the request is dropped.
+        let texture = test_texture(
+            &device,
+            wgpu::TextureFormat::Bgra8Unorm,
+            1,
+            1,
+            ResourceFlag::ALLOW_UAV,
+            "dropped_storage",
+        );
+        assert!(!texture
+            .info
+            .base_info
+            .flags
+            .contains(ResourceFlag::ALLOW_UAV));
+        assert!(!texture
+            .texture
+            .usage()
+            .contains(wgpu::TextureUsages::STORAGE_BINDING));
+    }
+
+    #[test]
+    fn usage_is_limited_to_format_support() {
+        let Some(device) = test_device() else {
+            return;
+        };
+        // Shared exponent formats can be sampled and copied, but never rendered to.
+        let texture = test_texture(
+            &device,
+            wgpu::TextureFormat::Rgb9e5Ufloat,
+            3,
+            1,
+            ResourceFlag::NONE,
+            "shared_exponent",
+        );
+        let usage = texture.texture.usage();
+        assert!(
+            usage.contains(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC)
+        );
+        assert!(!usage.contains(wgpu::TextureUsages::RENDER_ATTACHMENT));
+        assert!(matches!(
+            texture.generate_mipmaps(&device),
+            Err(ResourceError::InvalidCreateInfo(_))
+        ));
+    }
+
+    #[test]
+    fn bgra_pixels_are_swizzled_to_rgba() {
+        let mut pixels = vec![10, 20, 30, 40, 50, 60, 70, 80];
+        swizzle_bgra8_to_rgba8(&mut pixels);
+        assert_eq!(pixels, [30, 20, 10, 40, 70, 60, 50, 80]);
+        assert!(is_bgra8_format(wgpu::TextureFormat::Bgra8UnormSrgb));
+        assert!(!is_bgra8_format(wgpu::TextureFormat::Rgba8Unorm));
+    }
+}